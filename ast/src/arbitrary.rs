@@ -0,0 +1,86 @@
+//! Well-scoped de Bruijn terms generated from unstructured bytes, for fuzzing.
+//!
+//! `de_bruijn::Expr` can't implement `Arbitrary` directly because its nodes live in an
+//! `ExprBuilder`, so [`ArbitraryExpr`] is generated first and then built into one.
+
+use crate::de_bruijn::{ExprBuilder, ExprRef};
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+/// How deeply nested a generated term can be. Past this, only leaves are generated.
+const MAX_DEPTH: usize = 32;
+
+/// An owned de Bruijn term. Every `Var` refers to an enclosing binder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArbitraryExpr {
+    Var(usize),
+    Lam(Box<ArbitraryExpr>),
+    App(Box<ArbitraryExpr>, Box<ArbitraryExpr>),
+    U64(u64),
+    AddU64(Box<ArbitraryExpr>, Box<ArbitraryExpr>),
+    Let(Box<ArbitraryExpr>, Box<ArbitraryExpr>),
+    /// `Fix(body)` builds `Fix(Lam(body))`, where the `Lam` binds the fixpoint itself.
+    Fix(Box<ArbitraryExpr>),
+}
+
+impl ArbitraryExpr {
+    pub fn build<'builder, 'expr>(&self, builder: &'builder ExprBuilder<'expr>) -> ExprRef<'expr>
+    where
+        'builder: 'expr,
+    {
+        match self {
+            ArbitraryExpr::Var(n) => builder.mk_var(*n),
+            ArbitraryExpr::Lam(body) => builder.mk_lam(body.build(builder)),
+            ArbitraryExpr::App(l, r) => builder.mk_app(l.build(builder), r.build(builder)),
+            ArbitraryExpr::U64(n) => builder.mk_u64(*n),
+            ArbitraryExpr::AddU64(l, r) => builder.mk_addu64(l.build(builder), r.build(builder)),
+            ArbitraryExpr::Let(bound, body) => {
+                builder.mk_let(bound.build(builder), body.build(builder))
+            }
+            ArbitraryExpr::Fix(body) => builder.mk_fix(builder.mk_lam(body.build(builder))),
+        }
+    }
+}
+
+/// Generate a term under `scope` binders, with at most `depth` levels of nesting.
+fn arbitrary_at(u: &mut Unstructured, scope: usize, depth: usize) -> Result<ArbitraryExpr> {
+    let leaf = depth == 0 || u.is_empty();
+    let choice: u8 = if leaf {
+        u.int_in_range(0..=1)?
+    } else {
+        u.int_in_range(0..=6)?
+    };
+    let depth = depth.saturating_sub(1);
+    match choice {
+        0 if scope > 0 => Result::Ok(ArbitraryExpr::Var(u.int_in_range(0..=scope - 1)?)),
+        0 | 1 => Result::Ok(ArbitraryExpr::U64(u.arbitrary()?)),
+        2 => Result::Ok(ArbitraryExpr::Lam(Box::new(arbitrary_at(
+            u,
+            scope + 1,
+            depth,
+        )?))),
+        3 => Result::Ok(ArbitraryExpr::App(
+            Box::new(arbitrary_at(u, scope, depth)?),
+            Box::new(arbitrary_at(u, scope, depth)?),
+        )),
+        4 => Result::Ok(ArbitraryExpr::AddU64(
+            Box::new(arbitrary_at(u, scope, depth)?),
+            Box::new(arbitrary_at(u, scope, depth)?),
+        )),
+        5 => Result::Ok(ArbitraryExpr::Let(
+            Box::new(arbitrary_at(u, scope, depth)?),
+            Box::new(arbitrary_at(u, scope + 1, depth)?),
+        )),
+        _ => Result::Ok(ArbitraryExpr::Fix(Box::new(arbitrary_at(
+            u,
+            scope + 1,
+            depth,
+        )?))),
+    }
+}
+
+impl<'a> Arbitrary<'a> for ArbitraryExpr {
+    /// Generates closed terms.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_at(u, 0, MAX_DEPTH)
+    }
+}
@@ -1,60 +1,161 @@
 use crate::syntax;
+#[cfg(test)]
+use crate::types;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
 use typed_arena::Arena;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expr<'expr> {
     Var(usize),
     Lam(ExprRef<'expr>),
     App(ExprRef<'expr>, ExprRef<'expr>),
     U64(u64),
     AddU64(ExprRef<'expr>, ExprRef<'expr>),
+    /// A fixpoint. The body is a `Lam` whose argument is the fixpoint itself.
+    Fix(ExprRef<'expr>),
+    /// `Let(bound, body)` binds the value of `bound` as variable 0 in `body`.
+    Let(ExprRef<'expr>, ExprRef<'expr>),
+}
+
+/// A variable that was used outside the scope of any binder with its name.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnboundVariable<'src> {
+    pub name: &'src str,
+}
+
+/// Why [`from_ast_limited`] failed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConversionError<'src> {
+    /// The expression was nested more deeply than the limit.
+    TooDeep,
+    Unbound(Vec<UnboundVariable<'src>>),
+}
+
+/// The state of a conversion from the AST.
+struct Conversion<'src> {
+    /// The de Bruijn indices of the variables in scope. Shadowed variables are kept underneath
+    /// the variable that shadows them.
+    var_map: HashMap<&'src str, Vec<usize>>,
+    unbound: Vec<UnboundVariable<'src>>,
+    /// The name of each binder, in the order the binders appear in the expression.
+    names: Vec<&'src str>,
+    /// How many AST nodes enclose the one being converted.
+    depth: usize,
+    max_depth: Option<usize>,
+    too_deep: bool,
+}
+
+impl<'src> Conversion<'src> {
+    fn new(max_depth: Option<usize>) -> Self {
+        Conversion {
+            var_map: HashMap::new(),
+            unbound: Vec::new(),
+            names: Vec::new(),
+            depth: 0,
+            max_depth,
+            too_deep: false,
+        }
+    }
 }
 
+/// Convert `expr`, recording each unbound variable in `state` and replacing it with a
+/// placeholder. Expressions past the maximum depth are also replaced with a placeholder.
 fn __from_ast<'src, 'ast, 'builder, 'expr>(
-    var_map: &mut HashMap<&'src str, Vec<usize>>,
+    state: &mut Conversion<'src>,
     builder: &'builder ExprBuilder<'expr>,
     expr: syntax::ExprRef<'src, 'ast>,
 ) -> ExprRef<'expr>
 where
     'builder: 'expr,
 {
-    match expr {
-        syntax::Expr::Parens(inner) => __from_ast(var_map, builder, inner),
-        syntax::Expr::Ident(ident) => builder.mk_var(*var_map.get(ident).unwrap().last().unwrap()),
-        syntax::Expr::App(l, r) => builder.mk_app(
-            __from_ast(var_map, builder, l),
-            __from_ast(var_map, builder, r),
-        ),
-        syntax::Expr::Lam(arg, body) => {
-            for value in var_map.values_mut() {
-                value[0] += 1;
-            }
-            match var_map.get_mut(arg) {
-                Option::Some(value) => {
-                    value.push(0);
-                }
+    if let Option::Some(max_depth) = state.max_depth {
+        if state.depth >= max_depth {
+            state.too_deep = true;
+            return builder.mk_u64(0);
+        }
+    }
+    state.depth += 1;
+    let res = match expr {
+        // Annotations aren't checked yet, so they're dropped.
+        syntax::Expr::Parens(inner) | syntax::Expr::Ann(inner, _) => {
+            __from_ast(state, builder, inner)
+        }
+        syntax::Expr::Ident(ident) => {
+            match state.var_map.get(ident).and_then(|value| value.last()) {
+                Option::Some(index) => builder.mk_var(*index),
                 Option::None => {
-                    var_map.insert(arg, vec![0]);
-                }
-            }
-            let res = builder.mk_lam(__from_ast(var_map, builder, body));
-            match var_map.get_mut(arg) {
-                Option::Some(value) => {
-                    if value.len() <= 1 {
-                        var_map.remove(arg);
-                    } else {
-                        value.pop();
-                    }
+                    state.unbound.push(UnboundVariable { name: ident });
+                    builder.mk_u64(0)
                 }
-                Option::None => {}
             }
-            for value in var_map.values_mut() {
-                value[0] -= 1;
+        }
+        syntax::Expr::App(l, r) => {
+            builder.mk_app(__from_ast(state, builder, l), __from_ast(state, builder, r))
+        }
+        syntax::Expr::Lam(arg, body) => {
+            state.names.push(arg);
+            builder.mk_lam(__from_ast_binder(state, builder, arg, body))
+        }
+        syntax::Expr::Let(name, bound, body) => {
+            state.names.push(name);
+            builder.mk_let(
+                __from_ast(state, builder, bound),
+                __from_ast_binder(state, builder, name, body),
+            )
+        }
+    };
+    state.depth -= 1;
+    res
+}
+
+/// Convert `body`, which is in the scope of a new variable named `arg`. If `arg` is the
+/// [`syntax::WILDCARD`], the variable still takes up an index but can't be referred to.
+fn __from_ast_binder<'src, 'ast, 'builder, 'expr>(
+    state: &mut Conversion<'src>,
+    builder: &'builder ExprBuilder<'expr>,
+    arg: &'src str,
+    body: syntax::ExprRef<'src, 'ast>,
+) -> ExprRef<'expr>
+where
+    'builder: 'expr,
+{
+    for value in state.var_map.values_mut() {
+        value[0] += 1;
+    }
+    if arg == syntax::WILDCARD {
+        let res = __from_ast(state, builder, body);
+        for value in state.var_map.values_mut() {
+            value[0] -= 1;
+        }
+        return res;
+    }
+    match state.var_map.get_mut(arg) {
+        Option::Some(value) => {
+            value.push(0);
+        }
+        Option::None => {
+            state.var_map.insert(arg, vec![0]);
+        }
+    }
+    let res = __from_ast(state, builder, body);
+    match state.var_map.get_mut(arg) {
+        Option::Some(value) => {
+            if value.len() <= 1 {
+                state.var_map.remove(arg);
+            } else {
+                value.pop();
             }
-            res
         }
+        Option::None => {}
+    }
+    for value in state.var_map.values_mut() {
+        value[0] -= 1;
     }
+    res
 }
 
 pub fn from_ast<'src, 'ast, 'builder, 'expr>(
@@ -64,20 +165,445 @@ pub fn from_ast<'src, 'ast, 'builder, 'expr>(
 where
     'builder: 'expr,
 {
-    let mut var_map = HashMap::new();
-    __from_ast(&mut var_map, builder, expr)
+    match from_ast_checked(builder, expr) {
+        Result::Ok(expr) => expr,
+        Result::Err(unbound) => panic!("from_ast failed: unbound variable {}", unbound[0].name),
+    }
+}
+
+/// Like [`from_ast`], but reports every unbound variable instead of panicking at the first.
+/// They're listed in the order they appear in `expr`.
+pub fn from_ast_checked<'src, 'ast, 'builder, 'expr>(
+    builder: &'builder ExprBuilder<'expr>,
+    expr: syntax::ExprRef<'src, 'ast>,
+) -> Result<ExprRef<'expr>, Vec<UnboundVariable<'src>>>
+where
+    'builder: 'expr,
+{
+    let mut state = Conversion::new(Option::None);
+    let res = __from_ast(&mut state, builder, expr);
+    if state.unbound.is_empty() {
+        Result::Ok(res)
+    } else {
+        Result::Err(state.unbound)
+    }
+}
+
+/// Like [`from_ast_checked`], but also returns the name of each binder (`Lam` or `Let`) in the
+/// order that the binders appear in the result, so that the names can be recovered when the
+/// expression is displayed.
+pub fn from_ast_with_names<'src, 'ast, 'builder, 'expr>(
+    builder: &'builder ExprBuilder<'expr>,
+    expr: syntax::ExprRef<'src, 'ast>,
+) -> Result<(ExprRef<'expr>, Vec<&'src str>), Vec<UnboundVariable<'src>>>
+where
+    'builder: 'expr,
+{
+    let mut state = Conversion::new(Option::None);
+    let res = __from_ast(&mut state, builder, expr);
+    if state.unbound.is_empty() {
+        Result::Ok((res, state.names))
+    } else {
+        Result::Err(state.unbound)
+    }
+}
+
+/// Like [`from_ast_checked`], but gives up on expressions that are more than `max_depth` nodes
+/// deep (see [`syntax::depth`]) instead of risking a stack overflow.
+pub fn from_ast_limited<'src, 'ast, 'builder, 'expr>(
+    builder: &'builder ExprBuilder<'expr>,
+    expr: syntax::ExprRef<'src, 'ast>,
+    max_depth: usize,
+) -> Result<ExprRef<'expr>, ConversionError<'src>>
+where
+    'builder: 'expr,
+{
+    let mut state = Conversion::new(Option::Some(max_depth));
+    let res = __from_ast(&mut state, builder, expr);
+    if state.too_deep {
+        Result::Err(ConversionError::TooDeep)
+    } else if !state.unbound.is_empty() {
+        Result::Err(ConversionError::Unbound(state.unbound))
+    } else {
+        Result::Ok(res)
+    }
 }
 
 pub type ExprRef<'expr> = &'expr Expr<'expr>;
 
+/// The number of nodes in an expression.
+pub fn count_nodes<'expr>(expr: ExprRef<'expr>) -> usize {
+    match expr {
+        Expr::Var(_) => 1,
+        Expr::U64(_) => 1,
+        Expr::Lam(body) => 1 + count_nodes(body),
+        Expr::App(l, r) => 1 + count_nodes(l) + count_nodes(r),
+        Expr::AddU64(l, r) => 1 + count_nodes(l) + count_nodes(r),
+        Expr::Fix(body) => 1 + count_nodes(body),
+        Expr::Let(bound, body) => 1 + count_nodes(bound) + count_nodes(body),
+    }
+}
+
+/// The number of structurally distinct subexpressions in an expression, including the
+/// expression itself. This is the number of nodes the expression would occupy if it were built
+/// with [`ExprBuilder::with_hash_consing`].
+pub fn distinct_subterms<'expr>(expr: ExprRef<'expr>) -> usize {
+    fn go<'expr>(seen: &mut HashSet<ExprRef<'expr>>, expr: ExprRef<'expr>) {
+        // The children of a subexpression we've already seen have been visited too.
+        if !seen.insert(expr) {
+            return;
+        }
+        match expr {
+            Expr::Var(_) | Expr::U64(_) => {}
+            Expr::Lam(body) | Expr::Fix(body) => go(seen, body),
+            Expr::App(l, r) | Expr::AddU64(l, r) | Expr::Let(l, r) => {
+                go(seen, l);
+                go(seen, r);
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    go(&mut seen, expr);
+    seen.len()
+}
+
+/// Whether every `Var` in `expr` refers to a binder, assuming `expr` is under `depth` binders.
+/// A closed expression is well scoped at depth 0.
+pub fn is_well_scoped<'expr>(expr: ExprRef<'expr>, depth: usize) -> bool {
+    match expr {
+        Expr::Var(n) => *n < depth,
+        Expr::U64(_) => true,
+        Expr::Lam(body) => is_well_scoped(body, depth + 1),
+        Expr::App(l, r) => is_well_scoped(l, depth) && is_well_scoped(r, depth),
+        Expr::AddU64(l, r) => is_well_scoped(l, depth) && is_well_scoped(r, depth),
+        Expr::Fix(body) => is_well_scoped(body, depth),
+        Expr::Let(bound, body) => is_well_scoped(bound, depth) && is_well_scoped(body, depth + 1),
+    }
+}
+
+/// Split an application into its head and arguments, so that `f a b c` becomes
+/// `(f, [a, b, c])`. An expression that isn't an application is its own head.
+pub fn spine<'expr>(expr: ExprRef<'expr>) -> (ExprRef<'expr>, Vec<ExprRef<'expr>>) {
+    let mut head = expr;
+    let mut args = Vec::new();
+    while let Expr::App(l, r) = head {
+        args.push(*r);
+        head = l;
+    }
+    args.reverse();
+    (head, args)
+}
+
+/// The length of the longest path from the root of an expression to a leaf, counting both ends.
+pub fn depth<'expr>(expr: ExprRef<'expr>) -> usize {
+    match expr {
+        Expr::Var(_) => 1,
+        Expr::U64(_) => 1,
+        Expr::Lam(body) => 1 + depth(body),
+        Expr::App(l, r) => 1 + std::cmp::max(depth(l), depth(r)),
+        Expr::AddU64(l, r) => 1 + std::cmp::max(depth(l), depth(r)),
+        Expr::Fix(body) => 1 + depth(body),
+        Expr::Let(bound, body) => 1 + std::cmp::max(depth(bound), depth(body)),
+    }
+}
+
+/// Replace each addition of two literals with its result, working from the leaves up. Additions
+/// that would overflow are left as they are.
+pub fn fold_constants<'builder, 'expr>(
+    builder: &'builder ExprBuilder<'expr>,
+    expr: ExprRef<'expr>,
+) -> ExprRef<'expr>
+where
+    'builder: 'expr,
+{
+    match expr {
+        Expr::Var(_) => expr,
+        Expr::U64(_) => expr,
+        Expr::Lam(body) => builder.mk_lam(fold_constants(builder, body)),
+        Expr::App(l, r) => builder.mk_app(fold_constants(builder, l), fold_constants(builder, r)),
+        Expr::AddU64(l, r) => {
+            let l = fold_constants(builder, l);
+            let r = fold_constants(builder, r);
+            match (l, r) {
+                (Expr::U64(a), Expr::U64(b)) => match a.checked_add(*b) {
+                    Option::Some(n) => builder.mk_u64(n),
+                    Option::None => builder.mk_addu64(l, r),
+                },
+                _ => builder.mk_addu64(l, r),
+            }
+        }
+        Expr::Fix(body) => builder.mk_fix(fold_constants(builder, body)),
+        Expr::Let(bound, body) => builder.mk_let(
+            fold_constants(builder, bound),
+            fold_constants(builder, body),
+        ),
+    }
+}
+
+/// Add `by` to every variable in `expr` that's free when `expr` is under `depth` binders.
+fn shift<'builder, 'expr>(
+    builder: &'builder ExprBuilder<'expr>,
+    expr: ExprRef<'expr>,
+    by: usize,
+    depth: usize,
+) -> ExprRef<'expr>
+where
+    'builder: 'expr,
+{
+    match expr {
+        Expr::Var(n) if *n >= depth => builder.mk_var(n + by),
+        Expr::Var(_) | Expr::U64(_) => expr,
+        Expr::Lam(body) => builder.mk_lam(shift(builder, body, by, depth + 1)),
+        Expr::App(l, r) => {
+            builder.mk_app(shift(builder, l, by, depth), shift(builder, r, by, depth))
+        }
+        Expr::AddU64(l, r) => {
+            builder.mk_addu64(shift(builder, l, by, depth), shift(builder, r, by, depth))
+        }
+        Expr::Fix(body) => builder.mk_fix(shift(builder, body, by, depth)),
+        Expr::Let(bound, body) => builder.mk_let(
+            shift(builder, bound, by, depth),
+            shift(builder, body, by, depth + 1),
+        ),
+    }
+}
+
+/// Replace variable `depth` in `expr` with `arg`, where `expr` is the body of a binder under
+/// `depth` more binders. The binder is removed, so the variables bound outside it move down by one.
+fn substitute<'builder, 'expr>(
+    builder: &'builder ExprBuilder<'expr>,
+    expr: ExprRef<'expr>,
+    arg: ExprRef<'expr>,
+    depth: usize,
+) -> ExprRef<'expr>
+where
+    'builder: 'expr,
+{
+    match expr {
+        Expr::Var(n) if *n == depth => shift(builder, arg, depth, 0),
+        Expr::Var(n) if *n > depth => builder.mk_var(n - 1),
+        Expr::Var(_) | Expr::U64(_) => expr,
+        Expr::Lam(body) => builder.mk_lam(substitute(builder, body, arg, depth + 1)),
+        Expr::App(l, r) => builder.mk_app(
+            substitute(builder, l, arg, depth),
+            substitute(builder, r, arg, depth),
+        ),
+        Expr::AddU64(l, r) => builder.mk_addu64(
+            substitute(builder, l, arg, depth),
+            substitute(builder, r, arg, depth),
+        ),
+        Expr::Fix(body) => builder.mk_fix(substitute(builder, body, arg, depth)),
+        Expr::Let(bound, body) => builder.mk_let(
+            substitute(builder, bound, arg, depth),
+            substitute(builder, body, arg, depth + 1),
+        ),
+    }
+}
+
+/// Reduce `expr` to normal form, including under lambdas. Applied lambdas and lets are
+/// substituted away, and additions of literals are folded as in [`fold_constants`]. Fixpoints
+/// aren't unfolded, so an applied recursive function is left as it is.
+///
+/// Some expressions have no normal form, so at most `fuel` substitutions are made. If that isn't
+/// enough, the result is `None`.
+pub fn normalize<'builder, 'expr>(
+    builder: &'builder ExprBuilder<'expr>,
+    expr: ExprRef<'expr>,
+    fuel: usize,
+) -> Option<ExprRef<'expr>>
+where
+    'builder: 'expr,
+{
+    let mut fuel = fuel;
+    __normalize(builder, expr, &mut fuel)
+}
+
+/// Substitute `arg` into the body of a binder and normalize the result, if there's fuel left.
+fn __normalize_substitute<'builder, 'expr>(
+    builder: &'builder ExprBuilder<'expr>,
+    body: ExprRef<'expr>,
+    arg: ExprRef<'expr>,
+    fuel: &mut usize,
+) -> Option<ExprRef<'expr>>
+where
+    'builder: 'expr,
+{
+    *fuel = fuel.checked_sub(1)?;
+    __normalize(builder, substitute(builder, body, arg, 0), fuel)
+}
+
+fn __normalize<'builder, 'expr>(
+    builder: &'builder ExprBuilder<'expr>,
+    expr: ExprRef<'expr>,
+    fuel: &mut usize,
+) -> Option<ExprRef<'expr>>
+where
+    'builder: 'expr,
+{
+    match expr {
+        Expr::Var(_) | Expr::U64(_) => Option::Some(expr),
+        Expr::Lam(body) => Option::Some(builder.mk_lam(__normalize(builder, body, fuel)?)),
+        Expr::App(f, x) => match __normalize(builder, f, fuel)? {
+            Expr::Lam(body) => __normalize_substitute(builder, body, x, fuel),
+            f => Option::Some(builder.mk_app(f, __normalize(builder, x, fuel)?)),
+        },
+        Expr::AddU64(l, r) => {
+            let l = __normalize(builder, l, fuel)?;
+            let r = __normalize(builder, r, fuel)?;
+            Option::Some(match (l, r) {
+                (Expr::U64(a), Expr::U64(b)) => match a.checked_add(*b) {
+                    Option::Some(n) => builder.mk_u64(n),
+                    Option::None => builder.mk_addu64(l, r),
+                },
+                _ => builder.mk_addu64(l, r),
+            })
+        }
+        Expr::Fix(body) => Option::Some(builder.mk_fix(__normalize(builder, body, fuel)?)),
+        Expr::Let(bound, body) => __normalize_substitute(builder, body, bound, fuel),
+    }
+}
+
+/// A hash-consing key for a node whose children have already been hash-consed.
+///
+/// Structurally equal children are the same allocation, so the children are compared and hashed
+/// by address instead of by walking their subtrees. Each lookup is then constant time.
+#[derive(Clone, Copy)]
+struct NodeKey<'expr>(Expr<'expr>);
+
+impl<'expr> PartialEq for NodeKey<'expr> {
+    fn eq(&self, other: &Self) -> bool {
+        use std::ptr::eq;
+        match (self.0, other.0) {
+            (Expr::Var(a), Expr::Var(b)) => a == b,
+            (Expr::U64(a), Expr::U64(b)) => a == b,
+            (Expr::Lam(a), Expr::Lam(b)) | (Expr::Fix(a), Expr::Fix(b)) => eq(a, b),
+            (Expr::App(a1, a2), Expr::App(b1, b2))
+            | (Expr::AddU64(a1, a2), Expr::AddU64(b1, b2))
+            | (Expr::Let(a1, a2), Expr::Let(b1, b2)) => eq(a1, b1) && eq(a2, b2),
+            _ => false,
+        }
+    }
+}
+
+impl<'expr> Eq for NodeKey<'expr> {}
+
+impl<'expr> Hash for NodeKey<'expr> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        use std::ptr::hash;
+        std::mem::discriminant(&self.0).hash(state);
+        match self.0 {
+            Expr::Var(n) => n.hash(state),
+            Expr::U64(n) => n.hash(state),
+            Expr::Lam(body) | Expr::Fix(body) => hash(body, state),
+            Expr::App(l, r) | Expr::AddU64(l, r) | Expr::Let(l, r) => {
+                hash(l, state);
+                hash(r, state);
+            }
+        }
+    }
+}
+
 pub struct ExprBuilder<'expr> {
     arena: Arena<Expr<'expr>>,
+    /// Nodes that have already been built. Only present when hash-consing is enabled.
+    built: Option<RefCell<HashMap<NodeKey<'expr>, ExprRef<'expr>>>>,
 }
 
 impl<'expr> ExprBuilder<'expr> {
     pub fn new() -> Self {
         ExprBuilder {
             arena: Arena::new(),
+            built: Option::None,
+        }
+    }
+
+    /// Create a builder that never allocates the same node twice. Building a node that is
+    /// structurally equal to one that was built previously returns the previous allocation, so
+    /// shared subterms point to the same place.
+    ///
+    /// Nodes are looked up by their children's addresses, so sharing is only found for children
+    /// that were themselves built by this builder.
+    pub fn with_hash_consing() -> Self {
+        ExprBuilder {
+            arena: Arena::new(),
+            built: Option::Some(RefCell::new(HashMap::new())),
+        }
+    }
+
+    /// Run `f` with this builder, then drop every node it built. This lets one long-lived builder
+    /// be used for many short-lived expressions, such as the lines of a REPL:
+    ///
+    /// ```
+    /// use ast::de_bruijn::ExprBuilder;
+    ///
+    /// let mut builder = ExprBuilder::with_hash_consing();
+    /// for n in 0..3 {
+    ///     let size = builder.with_fresh_arena(|builder| {
+    ///         let expr = builder.mk_lam(builder.mk_var(n));
+    ///         ast::de_bruijn::count_nodes(expr)
+    ///     });
+    ///     assert_eq!(size, 2);
+    /// }
+    /// ```
+    ///
+    /// The `mk_*` functions borrow the builder for all of `'expr`, so a builder that has handed out
+    /// nodes can't otherwise be reset. Here `'expr` is local to `f`, and nodes can't escape it:
+    ///
+    /// ```compile_fail
+    /// use ast::de_bruijn::ExprBuilder;
+    ///
+    /// let mut builder = ExprBuilder::new();
+    /// let x = builder.with_fresh_arena(|builder| builder.mk_var(0));
+    /// ```
+    pub fn with_fresh_arena<R>(&mut self, f: impl for<'e> FnOnce(&'e ExprBuilder<'e>) -> R) -> R {
+        /// Empties the builder when `with_fresh_arena` returns or unwinds.
+        struct Reset<'a, 'expr>(&'a mut ExprBuilder<'expr>);
+
+        impl<'a, 'expr> Drop for Reset<'a, 'expr> {
+            fn drop(&mut self) {
+                self.0.reset()
+            }
+        }
+
+        self.reset();
+        let guard = Reset(self);
+        // SAFETY: The builder is empty, so it holds no nodes of lifetime `'expr`, and it's fine to
+        // use it at the shorter lifetime that `f` is given. Because `f` works for any `'e`, no
+        // node built by `f` can be part of `R`. Everything `f` builds is dropped by `guard`
+        // before the builder can be used at `'expr` again, even if `f` panics. `guard.0` isn't
+        // used until `f` returns, so the shared reference doesn't alias it.
+        let builder: &ExprBuilder =
+            unsafe { &*(guard.0 as *const ExprBuilder).cast::<ExprBuilder>() };
+        f(builder)
+    }
+
+    /// Replace the arena with an empty one, dropping every node built so far. The hash-consing
+    /// table keeps its capacity.
+    fn reset(&mut self) {
+        self.arena = Arena::new();
+        if let Option::Some(built) = &mut self.built {
+            built.get_mut().clear();
+        }
+    }
+
+    fn alloc<'builder>(&'builder self, expr: Expr<'expr>) -> ExprRef<'expr>
+    where
+        'builder: 'expr,
+    {
+        match &self.built {
+            Option::None => self.arena.alloc(expr),
+            Option::Some(built) => {
+                let mut built = built.borrow_mut();
+                match built.get(&NodeKey(expr)) {
+                    Option::Some(existing) => existing,
+                    Option::None => {
+                        let new = self.arena.alloc(expr);
+                        built.insert(NodeKey(expr), new);
+                        new
+                    }
+                }
+            }
         }
     }
 
@@ -85,28 +611,44 @@ impl<'expr> ExprBuilder<'expr> {
     where
         'builder: 'expr,
     {
-        self.arena.alloc(Expr::App(f, x))
+        self.alloc(Expr::App(f, x))
+    }
+
+    /// Apply `f` to each of `xs` in turn, so that `mk_apps(f, vec![a, b])` is `f a b`.
+    pub fn mk_apps<'builder>(
+        &'builder self,
+        f: ExprRef<'expr>,
+        xs: Vec<ExprRef<'expr>>,
+    ) -> ExprRef<'expr>
+    where
+        'builder: 'expr,
+    {
+        let mut expr = f;
+        for x in xs {
+            expr = self.mk_app(expr, x)
+        }
+        expr
     }
 
     pub fn mk_lam<'builder>(&'builder self, x: ExprRef<'expr>) -> ExprRef<'expr>
     where
         'builder: 'expr,
     {
-        self.arena.alloc(Expr::Lam(x))
+        self.alloc(Expr::Lam(x))
     }
 
     pub fn mk_var<'builder>(&'builder self, var: usize) -> ExprRef<'expr>
     where
         'builder: 'expr,
     {
-        self.arena.alloc(Expr::Var(var))
+        self.alloc(Expr::Var(var))
     }
 
     pub fn mk_u64<'builder>(&'builder self, var: u64) -> ExprRef<'expr>
     where
         'builder: 'expr,
     {
-        self.arena.alloc(Expr::U64(var))
+        self.alloc(Expr::U64(var))
     }
 
     pub fn mk_addu64<'builder>(
@@ -117,7 +659,25 @@ impl<'expr> ExprBuilder<'expr> {
     where
         'builder: 'expr,
     {
-        self.arena.alloc(Expr::AddU64(l, r))
+        self.alloc(Expr::AddU64(l, r))
+    }
+
+    pub fn mk_let<'builder>(
+        &'builder self,
+        bound: ExprRef<'expr>,
+        body: ExprRef<'expr>,
+    ) -> ExprRef<'expr>
+    where
+        'builder: 'expr,
+    {
+        self.alloc(Expr::Let(bound, body))
+    }
+
+    pub fn mk_fix<'builder>(&'builder self, body: ExprRef<'expr>) -> ExprRef<'expr>
+    where
+        'builder: 'expr,
+    {
+        self.alloc(Expr::Fix(body))
     }
 }
 
@@ -129,6 +689,21 @@ fn test_from_ast1() {
     assert_eq!(from_ast(&builder, input), output)
 }
 
+#[test]
+fn test_from_ast_wildcard2() {
+    // \x -> _
+    let input = &syntax::Expr::Lam("x", &syntax::Expr::Ident(syntax::WILDCARD));
+    let builder = ExprBuilder::new();
+    // `_` is unbound, and `free_vars` agrees.
+    assert_eq!(
+        from_ast_checked(&builder, input),
+        Result::Err(vec![UnboundVariable {
+            name: syntax::WILDCARD
+        }])
+    );
+    assert_eq!(syntax::free_vars(input), vec![syntax::WILDCARD])
+}
+
 #[test]
 fn test_from_ast2() {
     let input = &syntax::Expr::Lam("x", &syntax::Expr::Lam("y", &syntax::Expr::Ident("x")));
@@ -158,3 +733,494 @@ fn test_from_ast4() {
     let builder = ExprBuilder::new();
     assert_eq!(from_ast(&builder, input), output)
 }
+
+#[test]
+fn test_from_ast_checked1() {
+    let input = &syntax::Expr::Lam(
+        "x",
+        &syntax::Expr::App(&syntax::Expr::Ident("y"), &syntax::Expr::Ident("z")),
+    );
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        from_ast_checked(&builder, input),
+        Result::Err(vec![
+            UnboundVariable { name: "y" },
+            UnboundVariable { name: "z" }
+        ])
+    )
+}
+
+#[test]
+fn test_from_ast_checked2() {
+    let input = &syntax::Expr::Lam("x", &syntax::Expr::Ident("x"));
+    let output = &Expr::Lam(&Expr::Var(0));
+    let builder = ExprBuilder::new();
+    assert_eq!(from_ast_checked(&builder, input), Result::Ok(output))
+}
+
+#[test]
+fn test_from_ast_with_names1() {
+    let syntax_builder = syntax::ExprBuilder::new();
+    let builder = ExprBuilder::new();
+    // \x -> \y -> x
+    let expr = syntax_builder.mk_lam(
+        "x",
+        syntax_builder.mk_lam("y", syntax_builder.mk_ident("x")),
+    );
+    assert_eq!(
+        from_ast_with_names(&builder, expr),
+        Result::Ok((
+            builder.mk_lam(builder.mk_lam(builder.mk_var(1))),
+            vec!["x", "y"]
+        ))
+    )
+}
+
+#[test]
+fn test_from_ast_with_names2() {
+    let syntax_builder = syntax::ExprBuilder::new();
+    let builder = ExprBuilder::new();
+    // let f = \x -> x in \y -> f
+    let expr = syntax_builder.mk_let(
+        "f",
+        syntax_builder.mk_lam("x", syntax_builder.mk_ident("x")),
+        syntax_builder.mk_lam("y", syntax_builder.mk_ident("f")),
+    );
+    assert_eq!(
+        from_ast_with_names(&builder, expr).map(|(_, names)| names),
+        Result::Ok(vec!["f", "x", "y"])
+    )
+}
+
+#[test]
+fn test_from_ast_limited1() {
+    // \x -> \x -> ... -> x, nested 5000 deep
+    let syntax_builder = syntax::ExprBuilder::new();
+    let mut input = syntax_builder.mk_ident("x");
+    for _ in 0..4999 {
+        input = syntax_builder.mk_lam("x", input);
+    }
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        from_ast_limited(&builder, input, 1000),
+        Result::Err(ConversionError::TooDeep)
+    );
+}
+
+#[test]
+fn test_from_ast_limited3() {
+    // \x -> \y -> x (\z -> z y)
+    let syntax_builder = syntax::ExprBuilder::new();
+    let input = syntax_builder.mk_lam(
+        "x",
+        syntax_builder.mk_lam(
+            "y",
+            syntax_builder.mk_app(
+                syntax_builder.mk_ident("x"),
+                syntax_builder.mk_lam(
+                    "z",
+                    syntax_builder
+                        .mk_app(syntax_builder.mk_ident("z"), syntax_builder.mk_ident("y")),
+                ),
+            ),
+        ),
+    );
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        from_ast_limited(&builder, input, syntax::depth(input)),
+        Result::Ok(from_ast(&builder, input))
+    )
+}
+
+#[test]
+fn test_from_ast_limited2() {
+    let input = &syntax::Expr::Lam("x", &syntax::Expr::Ident("y"));
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        from_ast_limited(&builder, input, 2),
+        Result::Err(ConversionError::Unbound(vec![UnboundVariable {
+            name: "y"
+        }]))
+    );
+    assert_eq!(
+        from_ast_limited(&builder, input, 1),
+        Result::Err(ConversionError::TooDeep)
+    )
+}
+
+#[test]
+fn test_hash1() {
+    let builder = ExprBuilder::new();
+    let a = builder.mk_lam(builder.mk_app(builder.mk_var(0), builder.mk_u64(1)));
+    let b = builder.mk_lam(builder.mk_app(builder.mk_var(0), builder.mk_u64(1)));
+    let c = builder.mk_lam(builder.mk_app(builder.mk_var(0), builder.mk_u64(2)));
+    let mut set = HashSet::new();
+    set.insert(a);
+    set.insert(b);
+    assert_eq!(set.len(), 1);
+    set.insert(c);
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn test_hash_consing1() {
+    let builder = ExprBuilder::with_hash_consing();
+    let a = builder.mk_app(builder.mk_var(0), builder.mk_u64(1));
+    let b = builder.mk_app(builder.mk_var(0), builder.mk_u64(1));
+    assert!(std::ptr::eq(a, b));
+}
+
+#[test]
+fn test_hash_consing2() {
+    let builder = ExprBuilder::new();
+    let a = builder.mk_app(builder.mk_var(0), builder.mk_u64(1));
+    let b = builder.mk_app(builder.mk_var(0), builder.mk_u64(1));
+    assert_eq!(a, b);
+    assert!(!std::ptr::eq(a, b));
+}
+
+#[test]
+fn test_hash_consing3() {
+    let builder = ExprBuilder::with_hash_consing();
+    let build = || {
+        let mut expr = builder.mk_var(0);
+        for _ in 0..1000 {
+            expr = builder.mk_lam(builder.mk_app(expr, builder.mk_u64(1)));
+        }
+        expr
+    };
+    let a = build();
+    let b = build();
+    assert!(std::ptr::eq(a, b));
+
+    let x = builder.mk_var(0);
+    let app = builder.mk_app(x, x);
+    let add = builder.mk_addu64(x, x);
+    assert!(!std::ptr::eq(app, add));
+    assert_eq!(add, &Expr::AddU64(&Expr::Var(0), &Expr::Var(0)))
+}
+
+#[test]
+fn test_mk_apps1() {
+    let builder = ExprBuilder::new();
+    let f = builder.mk_var(0);
+    let a = builder.mk_u64(1);
+    let b = builder.mk_u64(2);
+    assert_eq!(
+        builder.mk_apps(f, vec![a, b]),
+        &Expr::App(&Expr::App(&Expr::Var(0), &Expr::U64(1)), &Expr::U64(2))
+    );
+    assert_eq!(builder.mk_apps(f, Vec::new()), f)
+}
+
+#[test]
+fn test_count_nodes1() {
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_lam(builder.mk_app(
+        builder.mk_var(0),
+        builder.mk_addu64(builder.mk_u64(1), builder.mk_u64(2)),
+    ));
+    assert_eq!(count_nodes(expr), 6)
+}
+
+#[test]
+fn test_distinct_subterms1() {
+    let builder = ExprBuilder::new();
+    // \n -> \c -> n
+    let nil = builder.mk_lam(builder.mk_lam(builder.mk_var(1)));
+    // \a -> \b -> \n -> \c -> c a (b n c)
+    let cons = builder.mk_lam(builder.mk_lam(builder.mk_lam(builder.mk_lam(builder.mk_app(
+        builder.mk_app(builder.mk_var(0), builder.mk_var(3)),
+        builder.mk_app(
+            builder.mk_app(builder.mk_var(2), builder.mk_var(1)),
+            builder.mk_var(0),
+        ),
+    )))));
+    // cons 0 (cons 1 (cons 2 (cons 3 (cons 4 (cons 5 nil)))))
+    let zero_to_5 = (0..6).rev().fold(nil, |tail, n| {
+        builder.mk_app(builder.mk_app(cons, builder.mk_u64(n)), tail)
+    });
+    assert_eq!(count_nodes(zero_to_5), 99);
+    // `cons` has 12 distinct subterms, `nil` adds 2 (its variable appears in `cons`), and each
+    // of the 6 elements adds a literal, a `cons n` and an application to the tail.
+    assert_eq!(distinct_subterms(zero_to_5), 32);
+}
+
+#[test]
+fn test_distinct_subterms2() {
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_lam(builder.mk_var(0));
+    assert_eq!(distinct_subterms(expr), count_nodes(expr));
+}
+
+#[test]
+fn test_depth1() {
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_lam(builder.mk_app(
+        builder.mk_var(0),
+        builder.mk_addu64(builder.mk_u64(1), builder.mk_u64(2)),
+    ));
+    assert_eq!(depth(expr), 4)
+}
+
+#[test]
+fn test_from_ast_let1() {
+    let input = &syntax::Expr::Lam(
+        "x",
+        &syntax::Expr::Let(
+            "y",
+            &syntax::Expr::Ident("x"),
+            &syntax::Expr::App(&syntax::Expr::Ident("x"), &syntax::Expr::Ident("y")),
+        ),
+    );
+    let output = &Expr::Lam(&Expr::Let(
+        &Expr::Var(0),
+        &Expr::App(&Expr::Var(1), &Expr::Var(0)),
+    ));
+    let builder = ExprBuilder::new();
+    assert_eq!(from_ast(&builder, input), output)
+}
+
+#[test]
+fn test_from_ast_let2() {
+    let input = &syntax::Expr::Let(
+        "x",
+        &syntax::Expr::Lam("x", &syntax::Expr::Ident("x")),
+        &syntax::Expr::Ident("x"),
+    );
+    let output = &Expr::Let(&Expr::Lam(&Expr::Var(0)), &Expr::Var(0));
+    let builder = ExprBuilder::new();
+    assert_eq!(from_ast(&builder, input), output)
+}
+
+#[test]
+fn test_from_ast_parens1() {
+    // \x -> (x)
+    let input = &syntax::Expr::Lam("x", &syntax::Expr::Parens(&syntax::Expr::Ident("x")));
+    let unparenthesized = &syntax::Expr::Lam("x", &syntax::Expr::Ident("x"));
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        from_ast(&builder, input),
+        from_ast(&builder, unparenthesized)
+    );
+    assert_eq!(from_ast(&builder, input), &Expr::Lam(&Expr::Var(0)))
+}
+
+#[test]
+fn test_from_ast_ann1() {
+    // \x -> x : a
+    let input = &syntax::Expr::Lam(
+        "x",
+        &syntax::Expr::Ann(&syntax::Expr::Ident("x"), &types::Type::TVar("a")),
+    );
+    let builder = ExprBuilder::new();
+    assert_eq!(from_ast(&builder, input), &Expr::Lam(&Expr::Var(0)))
+}
+
+#[test]
+fn test_from_ast_parens2() {
+    // ((\x -> x))
+    let input = &syntax::Expr::Parens(&syntax::Expr::Parens(&syntax::Expr::Lam(
+        "x",
+        &syntax::Expr::Ident("x"),
+    )));
+    let builder = ExprBuilder::new();
+    assert_eq!(from_ast(&builder, input), &Expr::Lam(&Expr::Var(0)))
+}
+
+#[test]
+fn test_from_ast_parens3() {
+    // \f -> \x -> (f x) ((x))
+    let input = &syntax::Expr::Lam(
+        "f",
+        &syntax::Expr::Lam(
+            "x",
+            &syntax::Expr::App(
+                &syntax::Expr::Parens(&syntax::Expr::App(
+                    &syntax::Expr::Ident("f"),
+                    &syntax::Expr::Ident("x"),
+                )),
+                &syntax::Expr::Parens(&syntax::Expr::Parens(&syntax::Expr::Ident("x"))),
+            ),
+        ),
+    );
+    let output = &Expr::Lam(&Expr::Lam(&Expr::App(
+        &Expr::App(&Expr::Var(1), &Expr::Var(0)),
+        &Expr::Var(0),
+    )));
+    let builder = ExprBuilder::new();
+    assert_eq!(from_ast(&builder, input), output)
+}
+
+#[test]
+fn test_with_fresh_arena1() {
+    let mut builder = ExprBuilder::with_hash_consing();
+    let size = builder.with_fresh_arena(|builder| {
+        let x = builder.mk_var(0);
+        let id = builder.mk_lam(x);
+        assert_eq!(id, &Expr::Lam(&Expr::Var(0)));
+        assert!(std::ptr::eq(x, builder.mk_var(0)));
+        count_nodes(id)
+    });
+    assert_eq!(size, 2);
+    assert_eq!(builder.arena.len(), 0);
+    assert!(builder.built.as_ref().unwrap().borrow().is_empty());
+
+    // The builder has been used, and can be used again.
+    builder.with_fresh_arena(|builder| {
+        let x = builder.mk_u64(1);
+        assert_eq!(
+            builder.mk_addu64(x, x),
+            &Expr::AddU64(&Expr::U64(1), &Expr::U64(1))
+        );
+        assert!(std::ptr::eq(x, builder.mk_u64(1)));
+    });
+    assert_eq!(builder.arena.len(), 0);
+}
+
+#[test]
+fn test_normalize1() {
+    let builder = ExprBuilder::new();
+    // \x -> (\y -> \z -> y) x
+    let expr = builder.mk_lam(builder.mk_app(
+        builder.mk_lam(builder.mk_lam(builder.mk_var(1))),
+        builder.mk_var(0),
+    ));
+    // \x -> \z -> x
+    assert_eq!(
+        normalize(&builder, expr, 100),
+        Option::Some(&Expr::Lam(&Expr::Lam(&Expr::Var(1))))
+    );
+
+    // let x = 1 in (\y -> x + y) 2
+    let expr = builder.mk_let(
+        builder.mk_u64(1),
+        builder.mk_app(
+            builder.mk_lam(builder.mk_addu64(builder.mk_var(1), builder.mk_var(0))),
+            builder.mk_u64(2),
+        ),
+    );
+    assert_eq!(normalize(&builder, expr, 100), Option::Some(&Expr::U64(3)));
+}
+
+#[test]
+fn test_normalize_free_vars() {
+    let builder = ExprBuilder::new();
+    // \x -> (\y -> \z -> y) #1, where #1 is free
+    let expr = builder.mk_lam(builder.mk_app(
+        builder.mk_lam(builder.mk_lam(builder.mk_var(1))),
+        builder.mk_var(1),
+    ));
+    // The free variable is now under two binders
+    assert_eq!(
+        normalize(&builder, expr, 100),
+        Option::Some(&Expr::Lam(&Expr::Lam(&Expr::Var(2))))
+    );
+}
+
+#[test]
+fn test_normalize_fuel() {
+    let builder = ExprBuilder::new();
+    // (\x -> x x) (\x -> x x)
+    let omega = builder.mk_lam(builder.mk_app(builder.mk_var(0), builder.mk_var(0)));
+    assert_eq!(
+        normalize(&builder, builder.mk_app(omega, omega), 100),
+        Option::None
+    );
+}
+
+#[test]
+fn test_fold_constants1() {
+    let builder = ExprBuilder::new();
+    // 1 + 2 + 3
+    let expr = builder.mk_addu64(
+        builder.mk_addu64(builder.mk_u64(1), builder.mk_u64(2)),
+        builder.mk_u64(3),
+    );
+    assert_eq!(fold_constants(&builder, expr), &Expr::U64(6))
+}
+
+#[test]
+fn test_fold_constants2() {
+    let builder = ExprBuilder::new();
+    // \x -> x + (1 + 2)
+    let expr = builder.mk_lam(builder.mk_addu64(
+        builder.mk_var(0),
+        builder.mk_addu64(builder.mk_u64(1), builder.mk_u64(2)),
+    ));
+    assert_eq!(
+        fold_constants(&builder, expr),
+        &Expr::Lam(&Expr::AddU64(&Expr::Var(0), &Expr::U64(3)))
+    )
+}
+
+#[test]
+fn test_fold_constants3() {
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_addu64(builder.mk_u64(u64::MAX), builder.mk_u64(1));
+    assert_eq!(fold_constants(&builder, expr), expr)
+}
+
+#[test]
+fn test_fold_constants4() {
+    let builder = ExprBuilder::new();
+    // x + 1, where x is free
+    let expr = builder.mk_addu64(builder.mk_var(0), builder.mk_u64(1));
+    assert_eq!(fold_constants(&builder, expr), expr)
+}
+
+#[test]
+fn test_from_ast_wildcard1() {
+    // \y -> \_ -> y
+    let input = &syntax::Expr::Lam(
+        "y",
+        &syntax::Expr::Lam(syntax::WILDCARD, &syntax::Expr::Ident("y")),
+    );
+    let output = &Expr::Lam(&Expr::Lam(&Expr::Var(1)));
+    let builder = ExprBuilder::new();
+    assert_eq!(from_ast(&builder, input), output)
+}
+
+#[test]
+fn test_spine1() {
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_app(
+        builder.mk_app(
+            builder.mk_app(builder.mk_var(3), builder.mk_var(2)),
+            builder.mk_var(1),
+        ),
+        builder.mk_var(0),
+    );
+    assert_eq!(
+        spine(expr),
+        (
+            &Expr::Var(3),
+            vec![&Expr::Var(2), &Expr::Var(1), &Expr::Var(0)]
+        )
+    )
+}
+
+#[test]
+fn test_spine2() {
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_addu64(builder.mk_u64(1), builder.mk_u64(2));
+    assert_eq!(spine(expr), (expr, Vec::new()))
+}
+
+#[test]
+fn test_is_well_scoped1() {
+    // \f -> \x -> f (x + 1)
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_lam(builder.mk_lam(builder.mk_app(
+        builder.mk_var(1),
+        builder.mk_addu64(builder.mk_var(0), builder.mk_u64(1)),
+    )));
+    assert!(is_well_scoped(expr, 0))
+}
+
+#[test]
+fn test_is_well_scoped2() {
+    let expr = &Expr::Lam(&Expr::Var(1));
+    assert!(!is_well_scoped(expr, 0));
+    assert!(is_well_scoped(expr, 1))
+}
@@ -1,2 +1,49 @@
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
 pub mod de_bruijn;
+#[cfg(feature = "serde")]
+pub mod owned;
 pub mod syntax;
+pub mod types;
+
+use syntax::{Expr, ExprRef};
+
+/// Whether two syntax trees are the same once their `Parens` nodes are removed, so that `(x)`
+/// equals `x`. Unlike [`syntax::alpha_eq`], variable names must match exactly.
+pub fn eq_ignoring_parens<'src, 'expr>(a: ExprRef<'src, 'expr>, b: ExprRef<'src, 'expr>) -> bool {
+    match (a, b) {
+        (Expr::Parens(a), _) => eq_ignoring_parens(a, b),
+        (_, Expr::Parens(b)) => eq_ignoring_parens(a, b),
+        (Expr::Ident(a), Expr::Ident(b)) => a == b,
+        (Expr::Lam(a_arg, a_body), Expr::Lam(b_arg, b_body)) => {
+            a_arg == b_arg && eq_ignoring_parens(a_body, b_body)
+        }
+        (Expr::App(a_l, a_r), Expr::App(b_l, b_r)) => {
+            eq_ignoring_parens(a_l, b_l) && eq_ignoring_parens(a_r, b_r)
+        }
+        (Expr::Let(a_name, a_bound, a_body), Expr::Let(b_name, b_bound, b_body)) => {
+            a_name == b_name
+                && eq_ignoring_parens(a_bound, b_bound)
+                && eq_ignoring_parens(a_body, b_body)
+        }
+        (Expr::Ann(a, a_ty), Expr::Ann(b, b_ty)) => a_ty == b_ty && eq_ignoring_parens(a, b),
+        _ => false,
+    }
+}
+
+#[test]
+fn test_eq_ignoring_parens1() {
+    let a = &Expr::Parens(&Expr::Ident("x"));
+    let b = &Expr::Ident("x");
+    assert!(eq_ignoring_parens(a, b));
+    assert!(eq_ignoring_parens(b, a))
+}
+
+#[test]
+fn test_eq_ignoring_parens2() {
+    let a = &Expr::App(&Expr::Ident("f"), &Expr::Parens(&Expr::Ident("x")));
+    let b = &Expr::App(&Expr::Ident("f"), &Expr::Ident("x"));
+    let c = &Expr::App(&Expr::Ident("f"), &Expr::Ident("y"));
+    assert!(eq_ignoring_parens(a, b));
+    assert!(!eq_ignoring_parens(a, c))
+}
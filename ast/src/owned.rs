@@ -0,0 +1,149 @@
+//! Owned copies of expressions, for deserializing.
+//!
+//! The `Expr` types implement `Serialize`, but they can't implement `Deserialize` because their
+//! nodes live in an `ExprBuilder`. Deserialize one of these instead, and then `reinflate` it into
+//! a builder. Each type has the same serialized form as the `Expr` it mirrors.
+
+use crate::{de_bruijn, syntax, types};
+use serde::{Deserialize, Serialize};
+
+/// An owned [`de_bruijn::Expr`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeBruijnExpr {
+    Var(usize),
+    Lam(Box<DeBruijnExpr>),
+    App(Box<DeBruijnExpr>, Box<DeBruijnExpr>),
+    U64(u64),
+    AddU64(Box<DeBruijnExpr>, Box<DeBruijnExpr>),
+    Fix(Box<DeBruijnExpr>),
+    Let(Box<DeBruijnExpr>, Box<DeBruijnExpr>),
+}
+
+impl DeBruijnExpr {
+    pub fn reinflate<'builder, 'expr>(
+        &self,
+        builder: &'builder de_bruijn::ExprBuilder<'expr>,
+    ) -> de_bruijn::ExprRef<'expr>
+    where
+        'builder: 'expr,
+    {
+        match self {
+            DeBruijnExpr::Var(n) => builder.mk_var(*n),
+            DeBruijnExpr::Lam(body) => builder.mk_lam(body.reinflate(builder)),
+            DeBruijnExpr::App(l, r) => builder.mk_app(l.reinflate(builder), r.reinflate(builder)),
+            DeBruijnExpr::U64(n) => builder.mk_u64(*n),
+            DeBruijnExpr::AddU64(l, r) => {
+                builder.mk_addu64(l.reinflate(builder), r.reinflate(builder))
+            }
+            DeBruijnExpr::Fix(body) => builder.mk_fix(body.reinflate(builder)),
+            DeBruijnExpr::Let(bound, body) => {
+                builder.mk_let(bound.reinflate(builder), body.reinflate(builder))
+            }
+        }
+    }
+}
+
+/// An owned [`syntax::Expr`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyntaxExpr {
+    Ident(String),
+    Lam(String, Box<SyntaxExpr>),
+    App(Box<SyntaxExpr>, Box<SyntaxExpr>),
+    Parens(Box<SyntaxExpr>),
+    Let(String, Box<SyntaxExpr>, Box<SyntaxExpr>),
+    Ann(Box<SyntaxExpr>, SyntaxType),
+}
+
+impl SyntaxExpr {
+    /// The identifiers in the result borrow from `self`.
+    pub fn reinflate<'src, 'builder, 'expr>(
+        &'src self,
+        builder: &'builder syntax::ExprBuilder<'src, 'expr>,
+    ) -> syntax::ExprRef<'src, 'expr>
+    where
+        'builder: 'expr,
+    {
+        match self {
+            SyntaxExpr::Ident(ident) => builder.mk_ident(ident),
+            SyntaxExpr::Lam(arg, body) => builder.mk_lam(arg, body.reinflate(builder)),
+            SyntaxExpr::App(l, r) => builder.mk_app(l.reinflate(builder), r.reinflate(builder)),
+            SyntaxExpr::Parens(inner) => builder.mk_parens(inner.reinflate(builder)),
+            SyntaxExpr::Let(name, bound, body) => {
+                builder.mk_let(name, bound.reinflate(builder), body.reinflate(builder))
+            }
+            SyntaxExpr::Ann(inner, ty) => {
+                builder.mk_ann(inner.reinflate(builder), ty.reinflate(builder.types()))
+            }
+        }
+    }
+}
+
+/// An owned [`types::Type`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyntaxType {
+    TVar(String),
+    TArrow(Box<SyntaxType>, Box<SyntaxType>),
+}
+
+impl SyntaxType {
+    /// The type variables in the result borrow from `self`.
+    pub fn reinflate<'src, 'builder, 'ty>(
+        &'src self,
+        builder: &'builder types::TypeBuilder<'src, 'ty>,
+    ) -> types::TypeRef<'src, 'ty>
+    where
+        'builder: 'ty,
+    {
+        match self {
+            SyntaxType::TVar(name) => builder.mk_tvar(name),
+            SyntaxType::TArrow(a, b) => {
+                builder.mk_tarrow(a.reinflate(builder), b.reinflate(builder))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_de_bruijn_round_trip() {
+    // let f = fix (\f -> \x -> f (x + 1)) in f 0
+    let builder = de_bruijn::ExprBuilder::new();
+    let expr = builder.mk_let(
+        builder.mk_fix(builder.mk_lam(builder.mk_lam(builder.mk_app(
+            builder.mk_var(1),
+            builder.mk_addu64(builder.mk_var(0), builder.mk_u64(1)),
+        )))),
+        builder.mk_app(builder.mk_var(0), builder.mk_u64(0)),
+    );
+    let json = serde_json::to_string(expr).unwrap();
+    let owned: DeBruijnExpr = serde_json::from_str(&json).unwrap();
+    let reinflated_builder = de_bruijn::ExprBuilder::new();
+    assert_eq!(owned.reinflate(&reinflated_builder), expr)
+}
+
+#[test]
+fn test_syntax_round_trip() {
+    let expr = &syntax::Expr::Lam(
+        "x",
+        &syntax::Expr::Parens(&syntax::Expr::App(
+            &syntax::Expr::Ident("f"),
+            &syntax::Expr::Ident("x"),
+        )),
+    );
+    let json = serde_json::to_string(expr).unwrap();
+    let owned: SyntaxExpr = serde_json::from_str(&json).unwrap();
+    let builder = syntax::ExprBuilder::new();
+    assert_eq!(owned.reinflate(&builder), expr)
+}
+
+#[test]
+fn test_syntax_round_trip_ann() {
+    // (\x -> x) : a -> a
+    let expr = &syntax::Expr::Ann(
+        &syntax::Expr::Parens(&syntax::Expr::Lam("x", &syntax::Expr::Ident("x"))),
+        &types::Type::TArrow(&types::Type::TVar("a"), &types::Type::TVar("a")),
+    );
+    let json = serde_json::to_string(expr).unwrap();
+    let owned: SyntaxExpr = serde_json::from_str(&json).unwrap();
+    let builder = syntax::ExprBuilder::new();
+    assert_eq!(owned.reinflate(&builder), expr)
+}
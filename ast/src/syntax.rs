@@ -1,26 +1,252 @@
+use crate::types::{TypeBuilder, TypeRef};
 use typed_arena::Arena;
 
 pub type ExprRef<'src, 'expr> = &'expr Expr<'src, 'expr>;
 
-#[derive(Debug, PartialEq, Eq)]
+/// A binder name that can't be referred to, as in `\_ -> e`.
+pub const WILDCARD: &str = "_";
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expr<'src, 'expr> {
     Ident(&'src str),
     Lam(&'src str, ExprRef<'src, 'expr>),
     App(ExprRef<'src, 'expr>, ExprRef<'src, 'expr>),
     Parens(ExprRef<'src, 'expr>),
+    /// `let name = bound in body`
+    Let(&'src str, ExprRef<'src, 'expr>, ExprRef<'src, 'expr>),
+    /// `expr : type`
+    Ann(ExprRef<'src, 'expr>, TypeRef<'src, 'expr>),
+}
+
+/// A top-level `name = body`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Definition<'src, 'expr> {
+    pub name: &'src str,
+    pub body: ExprRef<'src, 'expr>,
+}
+
+/// The number of nodes in an expression. `Parens` nodes are counted.
+pub fn count_nodes<'src, 'expr>(expr: ExprRef<'src, 'expr>) -> usize {
+    match expr {
+        Expr::Ident(_) => 1,
+        Expr::Lam(_, body) => 1 + count_nodes(body),
+        Expr::App(l, r) => 1 + count_nodes(l) + count_nodes(r),
+        Expr::Parens(inner) => 1 + count_nodes(inner),
+        Expr::Let(_, bound, body) => 1 + count_nodes(bound) + count_nodes(body),
+        Expr::Ann(inner, _) => 1 + count_nodes(inner),
+    }
+}
+
+/// Split an application into its head and arguments, so that `f a b c` becomes
+/// `(f, [a, b, c])`. An expression that isn't an application is its own head.
+/// `Parens` aren't looked through.
+pub fn spine<'src, 'expr>(
+    expr: ExprRef<'src, 'expr>,
+) -> (ExprRef<'src, 'expr>, Vec<ExprRef<'src, 'expr>>) {
+    let mut head = expr;
+    let mut args = Vec::new();
+    while let Expr::App(l, r) = head {
+        args.push(*r);
+        head = l;
+    }
+    args.reverse();
+    (head, args)
+}
+
+/// The length of the longest path from the root of an expression to a leaf, counting both ends.
+/// `Parens` nodes are counted.
+pub fn depth<'src, 'expr>(expr: ExprRef<'src, 'expr>) -> usize {
+    match expr {
+        Expr::Ident(_) => 1,
+        Expr::Lam(_, body) => 1 + depth(body),
+        Expr::App(l, r) => 1 + std::cmp::max(depth(l), depth(r)),
+        Expr::Parens(inner) => 1 + depth(inner),
+        Expr::Let(_, bound, body) => 1 + std::cmp::max(depth(bound), depth(body)),
+        Expr::Ann(inner, _) => 1 + depth(inner),
+    }
+}
+
+/// The identifiers that occur free in an expression, in the order they first appear. A
+/// [`WILDCARD`] binder doesn't bind anything, so a [`WILDCARD`] used as an expression is always
+/// reported, just as [`crate::de_bruijn::from_ast_checked`] reports it as unbound.
+pub fn free_vars<'src, 'expr>(expr: ExprRef<'src, 'expr>) -> Vec<&'src str> {
+    fn go<'src, 'expr>(
+        bound: &mut Vec<&'src str>,
+        free: &mut Vec<&'src str>,
+        expr: ExprRef<'src, 'expr>,
+    ) {
+        match expr {
+            Expr::Ident(name) => {
+                if (*name == WILDCARD || !bound.contains(name)) && !free.contains(name) {
+                    free.push(name)
+                }
+            }
+            Expr::Lam(arg, body) => {
+                bound.push(arg);
+                go(bound, free, body);
+                bound.pop();
+            }
+            Expr::App(l, r) => {
+                go(bound, free, l);
+                go(bound, free, r);
+            }
+            Expr::Parens(inner) | Expr::Ann(inner, _) => go(bound, free, inner),
+            Expr::Let(name, bound_expr, body) => {
+                go(bound, free, bound_expr);
+                bound.push(name);
+                go(bound, free, body);
+                bound.pop();
+            }
+        }
+    }
+
+    let mut free = Vec::new();
+    go(&mut Vec::new(), &mut free, expr);
+    free
+}
+
+/// Whether two expressions are the same up to the names of bound variables. `Parens` are
+/// ignored, so `(\x -> x)` is alpha-equivalent to `\y -> y`.
+pub fn alpha_eq<'src, 'expr>(a: ExprRef<'src, 'expr>, b: ExprRef<'src, 'expr>) -> bool {
+    fn strip_parens<'src, 'expr>(mut expr: ExprRef<'src, 'expr>) -> ExprRef<'src, 'expr> {
+        while let Expr::Parens(inner) = expr {
+            expr = inner;
+        }
+        expr
+    }
+
+    /// How many binders out the variable `name` was bound, or `None` if it's free.
+    fn binder_distance(scope: &[&str], name: &str) -> Option<usize> {
+        scope.iter().rev().position(|bound| *bound == name)
+    }
+
+    fn go<'src, 'expr>(
+        a_scope: &mut Vec<&'src str>,
+        b_scope: &mut Vec<&'src str>,
+        a: ExprRef<'src, 'expr>,
+        b: ExprRef<'src, 'expr>,
+    ) -> bool {
+        match (strip_parens(a), strip_parens(b)) {
+            (Expr::Ident(a_name), Expr::Ident(b_name)) => {
+                match (
+                    binder_distance(a_scope, a_name),
+                    binder_distance(b_scope, b_name),
+                ) {
+                    (Option::None, Option::None) => a_name == b_name,
+                    (a_distance, b_distance) => a_distance == b_distance,
+                }
+            }
+            (Expr::Lam(a_arg, a_body), Expr::Lam(b_arg, b_body)) => {
+                a_scope.push(a_arg);
+                b_scope.push(b_arg);
+                let res = go(a_scope, b_scope, a_body, b_body);
+                a_scope.pop();
+                b_scope.pop();
+                res
+            }
+            (Expr::App(a_l, a_r), Expr::App(b_l, b_r)) => {
+                go(a_scope, b_scope, a_l, b_l) && go(a_scope, b_scope, a_r, b_r)
+            }
+            (Expr::Let(a_name, a_bound, a_body), Expr::Let(b_name, b_bound, b_body)) => {
+                if !go(a_scope, b_scope, a_bound, b_bound) {
+                    return false;
+                }
+                a_scope.push(a_name);
+                b_scope.push(b_name);
+                let res = go(a_scope, b_scope, a_body, b_body);
+                a_scope.pop();
+                b_scope.pop();
+                res
+            }
+            (Expr::Ann(a, a_ty), Expr::Ann(b, b_ty)) => a_ty == b_ty && go(a_scope, b_scope, a, b),
+            _ => false,
+        }
+    }
+
+    go(&mut Vec::new(), &mut Vec::new(), a, b)
+}
+
+/// Copy an expression, replacing the free occurrences of `old` with `new`. Occurrences of `old`
+/// that are bound by a `Lam` or `Let` are left alone. This doesn't avoid capture, so `new` should
+/// not be bound anywhere that `old` occurs free.
+pub fn rename<'src, 'builder, 'expr>(
+    builder: &'builder ExprBuilder<'src, 'expr>,
+    old: &str,
+    new: &'src str,
+    expr: ExprRef<'src, 'expr>,
+) -> ExprRef<'src, 'expr>
+where
+    'builder: 'expr,
+{
+    match expr {
+        Expr::Ident(name) => builder.mk_ident(if *name == old { new } else { name }),
+        Expr::Lam(arg, body) => builder.mk_lam(
+            arg,
+            if *arg == old {
+                body
+            } else {
+                rename(builder, old, new, body)
+            },
+        ),
+        Expr::App(l, r) => {
+            builder.mk_app(rename(builder, old, new, l), rename(builder, old, new, r))
+        }
+        Expr::Parens(inner) => builder.mk_parens(rename(builder, old, new, inner)),
+        Expr::Let(name, bound, body) => builder.mk_let(
+            name,
+            rename(builder, old, new, bound),
+            if *name == old {
+                body
+            } else {
+                rename(builder, old, new, body)
+            },
+        ),
+        Expr::Ann(inner, ty) => builder.mk_ann(rename(builder, old, new, inner), ty),
+    }
+}
+
+/// Copy an expression without any of its `Parens` nodes. The tree already records how the
+/// expression is grouped, so this doesn't change its meaning, only how it's printed.
+pub fn strip_parens<'src, 'builder, 'expr>(
+    builder: &'builder ExprBuilder<'src, 'expr>,
+    expr: ExprRef<'src, 'expr>,
+) -> ExprRef<'src, 'expr>
+where
+    'builder: 'expr,
+{
+    match expr {
+        Expr::Ident(name) => builder.mk_ident(name),
+        Expr::Lam(arg, body) => builder.mk_lam(arg, strip_parens(builder, body)),
+        Expr::App(l, r) => builder.mk_app(strip_parens(builder, l), strip_parens(builder, r)),
+        Expr::Parens(inner) => strip_parens(builder, inner),
+        Expr::Let(name, bound, body) => builder.mk_let(
+            name,
+            strip_parens(builder, bound),
+            strip_parens(builder, body),
+        ),
+        Expr::Ann(inner, ty) => builder.mk_ann(strip_parens(builder, inner), ty),
+    }
 }
 
 pub struct ExprBuilder<'src, 'expr> {
     arena: Arena<Expr<'src, 'expr>>,
+    types: TypeBuilder<'src, 'expr>,
 }
 
 impl<'src, 'expr> ExprBuilder<'src, 'expr> {
     pub fn new() -> Self {
         ExprBuilder {
             arena: Arena::new(),
+            types: TypeBuilder::new(),
         }
     }
 
+    /// The builder for the types in annotations.
+    pub fn types(&self) -> &TypeBuilder<'src, 'expr> {
+        &self.types
+    }
+
     pub fn mk_app<'builder>(
         &'builder self,
         f: ExprRef<'src, 'expr>,
@@ -65,6 +291,29 @@ impl<'src, 'expr> ExprBuilder<'src, 'expr> {
         self.arena.alloc(Expr::Parens(inner))
     }
 
+    pub fn mk_let<'builder>(
+        &'builder self,
+        name: &'src str,
+        bound: ExprRef<'src, 'expr>,
+        body: ExprRef<'src, 'expr>,
+    ) -> ExprRef<'src, 'expr>
+    where
+        'builder: 'expr,
+    {
+        self.arena.alloc(Expr::Let(name, bound, body))
+    }
+
+    pub fn mk_ann<'builder>(
+        &'builder self,
+        expr: ExprRef<'src, 'expr>,
+        ty: TypeRef<'src, 'expr>,
+    ) -> ExprRef<'src, 'expr>
+    where
+        'builder: 'expr,
+    {
+        self.arena.alloc(Expr::Ann(expr, ty))
+    }
+
     pub fn mk_ident<'builder>(&'builder self, ident: &'src str) -> ExprRef<'src, 'expr>
     where
         'builder: 'expr,
@@ -72,3 +321,195 @@ impl<'src, 'expr> ExprBuilder<'src, 'expr> {
         self.arena.alloc(Expr::Ident(ident))
     }
 }
+
+#[test]
+fn test_count_nodes1() {
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_lam(
+        "x",
+        builder.mk_app(
+            builder.mk_ident("x"),
+            builder.mk_parens(builder.mk_ident("y")),
+        ),
+    );
+    assert_eq!(count_nodes(expr), 5)
+}
+
+#[test]
+fn test_depth1() {
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_lam(
+        "x",
+        builder.mk_app(
+            builder.mk_ident("x"),
+            builder.mk_parens(builder.mk_ident("y")),
+        ),
+    );
+    assert_eq!(depth(expr), 4)
+}
+
+#[test]
+fn test_depth2() {
+    let builder = ExprBuilder::new();
+    assert_eq!(depth(builder.mk_ident("x")), 1)
+}
+
+#[test]
+fn test_rename1() {
+    let builder = ExprBuilder::new();
+    // \x -> x y
+    let expr = builder.mk_lam(
+        "x",
+        builder.mk_app(builder.mk_ident("x"), builder.mk_ident("y")),
+    );
+    assert_eq!(rename(&builder, "x", "z", expr), expr)
+}
+
+#[test]
+fn test_rename2() {
+    let builder = ExprBuilder::new();
+    // x (\x -> x y) y
+    let expr = builder.mk_apps(
+        builder.mk_ident("x"),
+        vec![
+            builder.mk_parens(builder.mk_lam(
+                "x",
+                builder.mk_app(builder.mk_ident("x"), builder.mk_ident("y")),
+            )),
+            builder.mk_ident("y"),
+        ],
+    );
+    let expected = builder.mk_apps(
+        builder.mk_ident("z"),
+        vec![
+            builder.mk_parens(builder.mk_lam(
+                "x",
+                builder.mk_app(builder.mk_ident("x"), builder.mk_ident("y")),
+            )),
+            builder.mk_ident("y"),
+        ],
+    );
+    assert_eq!(rename(&builder, "x", "z", expr), expected)
+}
+
+#[test]
+fn test_rename3() {
+    let builder = ExprBuilder::new();
+    // let x = x in x
+    let expr = builder.mk_let("x", builder.mk_ident("x"), builder.mk_ident("x"));
+    assert_eq!(
+        rename(&builder, "x", "z", expr),
+        &Expr::Let("x", &Expr::Ident("z"), &Expr::Ident("x"))
+    )
+}
+
+#[test]
+fn test_strip_parens1() {
+    let builder = ExprBuilder::new();
+    // ((\x -> (x)))
+    let expr = builder.mk_parens(
+        builder.mk_parens(builder.mk_lam("x", builder.mk_parens(builder.mk_ident("x")))),
+    );
+    let stripped = strip_parens(&builder, expr);
+    assert_eq!(stripped, &Expr::Lam("x", &Expr::Ident("x")));
+    assert_eq!(strip_parens(&builder, stripped), stripped)
+}
+
+#[test]
+fn test_strip_parens2() {
+    let builder = ExprBuilder::new();
+    // f (g x) (y)
+    let expr = builder.mk_apps(
+        builder.mk_ident("f"),
+        vec![
+            builder.mk_parens(builder.mk_app(builder.mk_ident("g"), builder.mk_ident("x"))),
+            builder.mk_parens(builder.mk_ident("y")),
+        ],
+    );
+    let expected = builder.mk_apps(
+        builder.mk_ident("f"),
+        vec![
+            builder.mk_app(builder.mk_ident("g"), builder.mk_ident("x")),
+            builder.mk_ident("y"),
+        ],
+    );
+    assert_eq!(strip_parens(&builder, expr), expected)
+}
+
+#[test]
+fn test_free_vars1() {
+    let builder = ExprBuilder::new();
+    // \_ -> x (\y -> y x z)
+    let expr = builder.mk_lam(
+        WILDCARD,
+        builder.mk_app(
+            builder.mk_ident("x"),
+            builder.mk_parens(builder.mk_lam(
+                "y",
+                builder.mk_apps(
+                    builder.mk_ident("y"),
+                    vec![builder.mk_ident("x"), builder.mk_ident("z")],
+                ),
+            )),
+        ),
+    );
+    assert_eq!(free_vars(expr), vec!["x", "z"])
+}
+
+#[test]
+fn test_free_vars2() {
+    let builder = ExprBuilder::new();
+    // \_ -> _
+    let expr = builder.mk_lam(WILDCARD, builder.mk_ident(WILDCARD));
+    assert_eq!(free_vars(expr), vec![WILDCARD]);
+    // \x -> _
+    let expr = builder.mk_lam("x", builder.mk_ident(WILDCARD));
+    assert_eq!(free_vars(expr), vec![WILDCARD])
+}
+
+#[test]
+fn test_spine1() {
+    let builder = ExprBuilder::new();
+    let f = builder.mk_ident("f");
+    let args = vec![
+        builder.mk_ident("a"),
+        builder.mk_ident("b"),
+        builder.mk_ident("c"),
+    ];
+    let (head, spine_args) = spine(builder.mk_apps(f, args.clone()));
+    assert!(std::ptr::eq(head, f));
+    assert_eq!(spine_args, args)
+}
+
+#[test]
+fn test_spine2() {
+    let builder = ExprBuilder::new();
+    let expr = builder.mk_lam(
+        "x",
+        builder.mk_app(builder.mk_ident("x"), builder.mk_ident("x")),
+    );
+    assert_eq!(spine(expr), (expr, Vec::new()))
+}
+
+#[test]
+fn test_alpha_eq1() {
+    let a = &Expr::Lam("x", &Expr::Ident("x"));
+    let b = &Expr::Parens(&Expr::Lam("y", &Expr::Ident("y")));
+    assert!(alpha_eq(a, b))
+}
+
+#[test]
+fn test_alpha_eq2() {
+    let a = &Expr::Lam("x", &Expr::Lam("y", &Expr::Ident("x")));
+    let b = &Expr::Lam("x", &Expr::Lam("x", &Expr::Ident("x")));
+    assert!(!alpha_eq(a, b))
+}
+
+#[test]
+fn test_alpha_eq3() {
+    let a = &Expr::Lam("x", &Expr::Ident("y"));
+    let b = &Expr::Lam("z", &Expr::Ident("y"));
+    let c = &Expr::Lam("y", &Expr::Ident("y"));
+    assert!(alpha_eq(a, b));
+    assert!(!alpha_eq(a, c))
+}
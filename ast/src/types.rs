@@ -0,0 +1,49 @@
+use typed_arena::Arena;
+
+pub type TypeRef<'src, 'ty> = &'ty Type<'src, 'ty>;
+
+/// The types that can be written in an annotation. Nothing checks them yet.
+#[derive(Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Type<'src, 'ty> {
+    /// A type variable, such as `a`
+    TVar(&'src str),
+    /// `a -> b`
+    TArrow(TypeRef<'src, 'ty>, TypeRef<'src, 'ty>),
+}
+
+pub struct TypeBuilder<'src, 'ty> {
+    arena: Arena<Type<'src, 'ty>>,
+}
+
+impl<'src, 'ty> Default for TypeBuilder<'src, 'ty> {
+    fn default() -> Self {
+        TypeBuilder::new()
+    }
+}
+
+impl<'src, 'ty> TypeBuilder<'src, 'ty> {
+    pub fn new() -> Self {
+        TypeBuilder {
+            arena: Arena::new(),
+        }
+    }
+
+    pub fn mk_tvar<'builder>(&'builder self, name: &'src str) -> TypeRef<'src, 'ty>
+    where
+        'builder: 'ty,
+    {
+        self.arena.alloc(Type::TVar(name))
+    }
+
+    pub fn mk_tarrow<'builder>(
+        &'builder self,
+        a: TypeRef<'src, 'ty>,
+        b: TypeRef<'src, 'ty>,
+    ) -> TypeRef<'src, 'ty>
+    where
+        'builder: 'ty,
+    {
+        self.arena.alloc(Type::TArrow(a, b))
+    }
+}
@@ -1,12 +1,13 @@
 use ast::de_bruijn;
 use ast::syntax;
-use eval::heap::Heap;
+use eval::heap::HeapPool;
 use eval::stack::Stack;
 use eval::{eval, eval_loop};
 use lexer::Lexer;
 use parser::Parser;
 use span::SourceFiles;
 use std::path::Path;
+use std::time::Instant;
 
 fn build_eval_expr<'builder, 'value>(
     builder: &'builder de_bruijn::ExprBuilder<'value>,
@@ -92,17 +93,22 @@ fn run() -> bool {
         "eval" => {
             let builder = de_bruijn::ExprBuilder::new();
             let expr = build_eval_expr(&builder);
+            // Each iteration reuses the previous one's arena instead of allocating its own.
+            let pool = HeapPool::new(1024);
             for _ in 0..450000 {
-                let heap = Heap::with_capacity(1024);
-                let _ = eval(&heap, &Vec::new(), expr);
+                pool.with_heap(|heap| {
+                    let _ = eval(heap, &[], expr);
+                });
             }
         }
         "eval_loop" => {
             let builder = de_bruijn::ExprBuilder::new();
             let expr = build_eval_expr(&builder);
+            let pool = HeapPool::new(1024);
             for _ in 0..450000 {
-                let heap = Heap::with_capacity(1024);
-                let _ = eval_loop(&heap, expr);
+                pool.with_heap(|heap| {
+                    let _ = eval_loop(heap, expr);
+                });
             }
         }
         "parse" => {
@@ -138,6 +144,44 @@ fn run() -> bool {
                 };
             }
         }
+        "lex" => {
+            // Each file is lexed until roughly `LEX_BYTES` bytes have been processed, so that the
+            // timings for different sizes are comparable.
+            const LEX_BYTES: usize = 200_000_000;
+
+            let default_paths = [
+                String::from("./tokens_100.spd"),
+                String::from("./tokens_1000.spd"),
+                String::from("./tokens_10000.spd"),
+            ];
+            let paths = if args.len() > 2 {
+                &args[2..]
+            } else {
+                &default_paths[..]
+            };
+
+            let mut src_files = SourceFiles::new();
+            for path in paths {
+                let (_, file_name) = src_files.load_source_file(Path::new(path));
+                let src_file = src_files.get_by_name(&file_name);
+                let iterations = LEX_BYTES / std::cmp::max(1, src_file.content.len());
+
+                let start = Instant::now();
+                for _ in 0..iterations {
+                    let lexer = Lexer::from_source_file(src_file);
+                    if let Result::Err(err) = lexer.tokenize() {
+                        err.reportable().report(&src_files);
+                        return false;
+                    }
+                }
+                println!(
+                    "{}: {} iterations in {:?}",
+                    path,
+                    iterations,
+                    start.elapsed()
+                );
+            }
+        }
         arg => panic!("Unexpected command line argument {:?}", arg),
     }
 
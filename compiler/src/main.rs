@@ -1,44 +1,201 @@
-use ast::ExprBuilder;
+use ast::de_bruijn::{self, UnboundVariable};
+use ast::syntax::{self, ExprBuilder};
+use errors::{Diagnostic, Highlight};
+use eval::heap::Heap;
+use eval::try_eval_loop_with_fuel;
 use lexer::Lexer;
 use parser::Parser;
-use span::SourceFiles;
+use span::{Offset, SourceFile, SourceFiles, Span};
 use std::path::Path;
+use std::time::Instant;
+
+/// Print how long `phase` took to stderr, if `timings` is enabled.
+fn report_timing(timings: bool, phase: &str, start: Instant) {
+    if timings {
+        eprintln!("{}: {:?}", phase, start.elapsed());
+    }
+}
+
+/// Report the first unbound variable, and mention the rest in a note. Identifiers borrow from
+/// the source, so their positions can be recovered from where they point into `src_file`.
+fn unbound_error(src_file: &SourceFile, unbound: &[UnboundVariable]) -> Diagnostic {
+    let first = &unbound[0];
+    let start = first.name.as_ptr() as usize - src_file.content.as_ptr() as usize;
+    let mut err: Diagnostic = errors::Error::at(Highlight::Span(Span {
+        start: src_file.start.add(start as u32),
+        length: Offset(first.name.len() as u32),
+    }))
+    .message(format!("Unbound variable '{}'", first.name))
+    .into();
+    if unbound.len() > 1 {
+        let rest: Vec<String> = unbound[1..]
+            .iter()
+            .map(|var| format!("'{}'", var.name))
+            .collect();
+        err = err.note(format!("Also unbound: {}", rest.join(", ")));
+    }
+    err
+}
+
+/// How many steps evaluation can take before giving up, so that a program that doesn't terminate
+/// can't hang the compiler.
+const EVAL_FUEL: usize = 1_000_000;
+
+/// Report an error found while evaluating `src_file`. The evaluated expression doesn't keep
+/// track of where it came from, so the error points at the start of the file.
+fn eval_error(src_file: &SourceFile, message: String) -> Diagnostic {
+    errors::Error::at(Highlight::Point(src_file.start))
+        .message(message)
+        .into()
+}
+
+/// What to do with the input file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Compile,
+    /// `--check`
+    Check,
+    /// `--debruijn`
+    DeBruijn,
+}
+
+const USAGE: &str = "usage: compiler [--timings] [--check | --debruijn] FILE";
 
 fn run() -> bool {
-    let args: Vec<String> = std::env::args().collect();
-    let path = Path::new(&args[1]);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mode_flag = |arg: &str| match arg {
+        "--check" => Option::Some(Mode::Check),
+        "--debruijn" => Option::Some(Mode::DeBruijn),
+        _ => Option::None,
+    };
+    let is_flag = |arg: &String| arg == "--timings" || mode_flag(arg).is_some();
+    let timings = args.iter().any(|arg| arg == "--timings");
+    let modes: Vec<Mode> = args.iter().filter_map(|arg| mode_flag(arg)).collect();
+    let mode = match modes.as_slice() {
+        [] => Mode::Compile,
+        [mode] => *mode,
+        _ => {
+            eprintln!("{}", USAGE);
+            return false;
+        }
+    };
+    let path = match args.iter().find(|arg| !is_flag(arg)) {
+        Option::Some(path) => Path::new(path),
+        Option::None => {
+            eprintln!("{}", USAGE);
+            return false;
+        }
+    };
 
     let mut src_files = SourceFiles::new();
     let (_, file_name) = src_files.load_source_file(path);
 
     let src_file = src_files.get_by_name(&file_name);
 
-    let tokens = {
-        let lexer = Lexer::from_source_file(src_file);
-        match lexer.tokenize() {
-            Result::Err(err) => {
-                err.reportable().report(&src_files);
-                return false;
-            }
-            Result::Ok(tokens) => tokens,
-        }
+    let res = match mode {
+        Mode::Compile => compile(src_file, timings),
+        Mode::Check => check(src_file, timings),
+        Mode::DeBruijn => print_de_bruijn(src_file, timings),
     };
+    match res {
+        Result::Err(err) => {
+            err.report(&src_files);
+            false
+        }
+        Result::Ok(()) => true,
+    }
+}
 
+fn compile(src_file: &SourceFile, timings: bool) -> Result<(), Diagnostic> {
     let builder = ExprBuilder::new();
-    let ast = {
-        let mut parser = Parser::new(&builder, &tokens);
-        match parser.parse_expr_eof() {
+    let ast = parse(src_file, timings, &builder)?;
+
+    println!("{:?}", ast);
+
+    // Conversion and evaluation aren't part of the normal output yet, so they only run when
+    // they're being timed.
+    if timings {
+        let de_bruijn_builder = de_bruijn::ExprBuilder::new();
+        let expr = convert_ast(src_file, timings, &de_bruijn_builder, ast)?;
+
+        let start = Instant::now();
+        let heap = Heap::with_capacity(1024 * 1024);
+        let res = try_eval_loop_with_fuel(&heap, expr, EVAL_FUEL);
+        report_timing(timings, "evaluation", start);
+        match res {
+            Result::Ok(Option::Some(_)) => {}
+            Result::Ok(Option::None) => {
+                return Result::Err(eval_error(
+                    src_file,
+                    format!("Evaluation didn't finish within {} steps", EVAL_FUEL),
+                ))
+            }
             Result::Err(err) => {
-                err.reportable().report(&src_files);
-                return false;
+                return Result::Err(eval_error(src_file, format!("Evaluation failed: {}", err)))
             }
-            Result::Ok(expr) => expr,
         }
-    };
+    }
 
-    println!("{:?}", ast);
+    Result::Ok(())
+}
+
+/// Lex and parse an expression.
+fn parse<'src, 'expr>(
+    src_file: &'src SourceFile,
+    timings: bool,
+    builder: &'expr ExprBuilder<'src, 'expr>,
+) -> Result<syntax::ExprRef<'src, 'expr>, Diagnostic> {
+    let start = Instant::now();
+    let tokens = Lexer::from_source_file(src_file).tokenize()?;
+    report_timing(timings, "lexing", start);
+
+    let start = Instant::now();
+    let ast = Parser::new(builder, &tokens).parse_expr_eof()?;
+    report_timing(timings, "parsing", start);
+
+    Result::Ok(ast)
+}
+
+/// Lex, parse and convert to de Bruijn indices, reporting unbound variables.
+fn convert<'src, 'expr>(
+    src_file: &'src SourceFile,
+    timings: bool,
+    builder: &'expr ExprBuilder<'src, 'expr>,
+    de_bruijn_builder: &'expr de_bruijn::ExprBuilder<'expr>,
+) -> Result<de_bruijn::ExprRef<'expr>, Diagnostic> {
+    let ast = parse(src_file, timings, builder)?;
+    convert_ast(src_file, timings, de_bruijn_builder, ast)
+}
+
+/// Convert a parsed expression to de Bruijn indices, reporting unbound variables.
+fn convert_ast<'src, 'expr>(
+    src_file: &'src SourceFile,
+    timings: bool,
+    de_bruijn_builder: &'expr de_bruijn::ExprBuilder<'expr>,
+    ast: syntax::ExprRef<'src, 'expr>,
+) -> Result<de_bruijn::ExprRef<'expr>, Diagnostic> {
+    let start = Instant::now();
+    let res = de_bruijn::from_ast_checked(de_bruijn_builder, ast);
+    report_timing(timings, "de Bruijn conversion", start);
+
+    res.map_err(|unbound| unbound_error(src_file, &unbound))
+}
 
-    true
+/// Lex, parse and convert to de Bruijn indices, without evaluating. Nothing is printed unless
+/// there's an error.
+fn check(src_file: &SourceFile, timings: bool) -> Result<(), Diagnostic> {
+    let builder = ExprBuilder::new();
+    let de_bruijn_builder = de_bruijn::ExprBuilder::new();
+    convert(src_file, timings, &builder, &de_bruijn_builder).map(|_| ())
+}
+
+/// Convert to de Bruijn indices and print the result.
+fn print_de_bruijn(src_file: &SourceFile, timings: bool) -> Result<(), Diagnostic> {
+    let builder = ExprBuilder::new();
+    let de_bruijn_builder = de_bruijn::ExprBuilder::new();
+    let expr = convert(src_file, timings, &builder, &de_bruijn_builder)?;
+    println!("{}", pretty::pretty_de_bruijn(expr));
+    Result::Ok(())
 }
 
 fn main() {
@@ -0,0 +1,33 @@
+use std::process::Command;
+
+fn check(example: &str) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg("--check")
+        .arg(format!(
+            "{}/examples/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            example
+        ))
+        .output()
+        .unwrap()
+}
+
+/// `omega.spd` is well-formed and well-scoped. It doesn't terminate, so this also shows that it
+/// isn't evaluated.
+#[test]
+fn test_check_well_formed() {
+    let output = check("omega.spd");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty())
+}
+
+#[test]
+fn test_check_unbound() {
+    let output = check("apply.spd");
+    assert!(!output.status.success());
+    // errors are reported on stdout
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Unbound variable 'x'"), "{}", stdout);
+    assert!(stdout.contains("Also unbound: 'y'"), "{}", stdout)
+}
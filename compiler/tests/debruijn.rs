@@ -0,0 +1,42 @@
+use std::process::Command;
+
+fn debruijn(example: &str) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg("--debruijn")
+        .arg(format!(
+            "{}/examples/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            example
+        ))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_debruijn_id() {
+    let output = debruijn("id.spd");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\\. #0\n")
+}
+
+#[test]
+fn test_debruijn_unbound() {
+    let output = debruijn("apply.spd");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Unbound variable 'x'"), "{}", stdout)
+}
+
+#[test]
+fn test_debruijn_conflicting_modes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg("--debruijn")
+        .arg("--check")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/id.spd"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("usage:"))
+}
@@ -0,0 +1,86 @@
+use std::process::Command;
+
+#[test]
+fn test_timings1() {
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg("--timings")
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/id_apply.spd"
+        ))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let phases: Vec<&str> = stderr
+        .lines()
+        .map(|line| line.split(": ").next().unwrap())
+        .collect();
+    assert_eq!(
+        phases,
+        vec!["lexing", "parsing", "de Bruijn conversion", "evaluation"]
+    )
+}
+
+#[test]
+fn test_timings2() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/id_apply.spd");
+    let with_timings = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg("--timings")
+        .arg(path)
+        .output()
+        .unwrap();
+    let without_timings = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg(path)
+        .output()
+        .unwrap();
+    assert_eq!(with_timings.stdout, without_timings.stdout);
+    assert!(without_timings.stderr.is_empty())
+}
+
+#[test]
+fn test_timings_unbound() {
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg("--timings")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/apply.spd"))
+        .output()
+        .unwrap();
+    // An error, not a panic
+    assert_eq!(output.status.code(), Option::Some(1));
+    // errors are reported on stdout
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Unbound variable 'x'"), "{}", stdout);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let phases: Vec<&str> = stderr
+        .lines()
+        .map(|line| line.split(": ").next().unwrap())
+        .collect();
+    assert_eq!(phases, vec!["lexing", "parsing", "de Bruijn conversion"])
+}
+
+#[test]
+fn test_timings_diverges() {
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg("--timings")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/omega.spd"))
+        .output()
+        .unwrap();
+    // An error, not a hang or a panic
+    assert_eq!(output.status.code(), Option::Some(1));
+    // errors are reported on stdout
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("Evaluation didn't finish within 1000000 steps"),
+        "{}",
+        stdout
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let phases: Vec<&str> = stderr
+        .lines()
+        .map(|line| line.split(": ").next().unwrap())
+        .collect();
+    assert_eq!(
+        phases,
+        vec!["lexing", "parsing", "de Bruijn conversion", "evaluation"]
+    )
+}
@@ -1,10 +1,14 @@
 use span::{Offset, SourceFiles, Span};
+use std::fmt;
 use std::io;
 use std::io::Write;
 
 pub enum Highlight {
     Point(Offset),
     Span(Span),
+    /// Several highlights reported together. Only the highlights on the same line as the
+    /// earliest one are rendered.
+    Multi(Vec<Highlight>),
 }
 
 impl Highlight {
@@ -13,6 +17,11 @@ impl Highlight {
         match self {
             Highlight::Point(start) => *start,
             Highlight::Span(span) => span.start,
+            Highlight::Multi(highlights) => highlights
+                .iter()
+                .map(|highlight| highlight.start())
+                .min()
+                .expect("start failed: empty Multi highlight"),
         }
     }
 
@@ -21,21 +30,125 @@ impl Highlight {
         match self {
             Highlight::Point(_) => Offset(1),
             Highlight::Span(span) => span.length,
+            Highlight::Multi(_) => Offset(self.end().to_u32() - self.start().to_u32()),
         }
     }
 
     #[inline]
     pub fn end(&self) -> Offset {
-        self.start().add(self.len().to_u32())
+        match self {
+            Highlight::Multi(highlights) => highlights
+                .iter()
+                .map(|highlight| highlight.end())
+                .max()
+                .expect("end failed: empty Multi highlight"),
+            _ => self.start().add(self.len().to_u32()),
+        }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
 pub struct Error {
     pub highlight: Highlight,
     pub message: String,
 }
 
-fn highlight<'src>(line: &'src str, line_offset: Offset, region: Highlight) -> String {
+impl Error {
+    /// Start building an error at `highlight`, with an empty message. Adding a note or a
+    /// severity turns it into a [`Diagnostic`].
+    ///
+    /// ```
+    /// use errors::{Error, Highlight, Severity};
+    /// use span::Offset;
+    ///
+    /// let diagnostic = Error::at(Highlight::Point(Offset(0)))
+    ///     .message("Unused variable")
+    ///     .note("Prefix it with '_' to silence this warning")
+    ///     .severity(Severity::Warning);
+    /// ```
+    pub fn at(highlight: Highlight) -> Self {
+        Error {
+            highlight,
+            message: String::new(),
+        }
+    }
+
+    pub fn message<S: Into<String>>(mut self, message: S) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    pub fn note<S: Into<String>>(self, note: S) -> Diagnostic {
+        Diagnostic::from(self).note(note)
+    }
+
+    pub fn severity(self, severity: Severity) -> Diagnostic {
+        Diagnostic::from(self).severity(severity)
+    }
+}
+
+/// An [`Error`] with notes and a severity. Every `Error` can be used as a diagnostic with no notes
+/// and [`Severity::Error`].
+pub struct Diagnostic {
+    pub error: Error,
+    /// Extra information, displayed after the message.
+    pub notes: Vec<String>,
+    pub severity: Severity,
+}
+
+impl From<Error> for Diagnostic {
+    fn from(error: Error) -> Self {
+        Diagnostic {
+            error,
+            notes: Vec::new(),
+            severity: Severity::Error,
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn message<S: Into<String>>(mut self, message: S) -> Self {
+        self.error.message = message.into();
+        self
+    }
+
+    pub fn note<S: Into<String>>(mut self, note: S) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn report(self, src_files: &SourceFiles) {
+        let report = build_report(src_files, self);
+        let _ = io::stdout().write(report.to_string().as_bytes()).unwrap();
+    }
+}
+
+/// How a span is underlined. A single character is marked with `^` either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaretStyle {
+    /// `^^^^^`
+    #[default]
+    Uniform,
+    /// `^~~~^`, which makes it clearer where a long span starts and ends.
+    Ends,
+}
+
+fn highlight<'src>(
+    line: &'src str,
+    line_offset: Offset,
+    region: Highlight,
+    style: CaretStyle,
+) -> String {
     let mut string = String::new();
     let mut pos: usize = 0;
     match region {
@@ -43,13 +156,19 @@ fn highlight<'src>(line: &'src str, line_offset: Offset, region: Highlight) -> S
             let offset = offset.to_usize() - line_offset.to_usize();
             for c in line.chars() {
                 if pos == offset {
-                    string.push('^');
                     break;
-                } else {
-                    string.push(' ');
                 }
+                string.push(' ');
                 pos += c.len_utf8();
             }
+            // A point just past the last character, such as the end of input, is marked there.
+            if pos == offset {
+                string.push('^');
+            }
+        }
+        // An empty span has nothing to underline, so mark where it starts instead.
+        Highlight::Span(span) if span.is_empty() => {
+            string = highlight(line, line_offset, Highlight::Point(span.start), style);
         }
         Highlight::Span(span) => {
             let mut in_range = false;
@@ -57,11 +176,15 @@ fn highlight<'src>(line: &'src str, line_offset: Offset, region: Highlight) -> S
                 let line_offset = line_offset.to_usize();
                 let start_offset = span.start.to_usize() - line_offset;
                 let end_offset = span.end().to_usize() - line_offset;
+                let is_last = pos + c.len_utf8() == end_offset;
                 if in_range {
                     if pos == end_offset {
                         break;
                     } else {
-                        string.push('^')
+                        string.push(match style {
+                            CaretStyle::Ends if !is_last => '~',
+                            _ => '^',
+                        })
                     }
                 } else {
                     if pos == start_offset {
@@ -74,15 +197,75 @@ fn highlight<'src>(line: &'src str, line_offset: Offset, region: Highlight) -> S
                 pos += c.len_utf8();
             }
         }
+        Highlight::Multi(highlights) => {
+            let line_start = line_offset.to_usize();
+            let line_end = line_start + line.len();
+            let mut carets: Vec<u8> = Vec::new();
+            for region in highlights {
+                let start = region.start().to_usize();
+                if start < line_start || line_end < start {
+                    continue;
+                }
+                let region_string = highlight(line, line_offset, region, style);
+                for (ix, b) in region_string.bytes().enumerate() {
+                    if ix < carets.len() {
+                        // `^` wins over `~`, which wins over `' '`
+                        if b == b'^' || carets[ix] == b' ' {
+                            carets[ix] = b;
+                        }
+                    } else {
+                        carets.push(b);
+                    }
+                }
+            }
+            // `carets` only contains `' '`, `'~'` and `'^'`
+            string = String::from_utf8(carets).unwrap();
+        }
     }
     string
 }
 
-pub fn __build_report(src_files: &SourceFiles, error: Error) -> [String; 5] {
+/// A rendered error report, one field per output line. The fields don't include trailing
+/// newlines; the `Display` impl adds them.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Report {
+    pub file: String,
+    pub gutter: String,
+    pub source_line: String,
+    pub caret_line: String,
+    /// Followed by the diagnostic's notes, each on its own line and prefixed by `note: `.
+    pub message: String,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.file)?;
+        writeln!(f, "{}", self.gutter)?;
+        writeln!(f, "{}", self.source_line)?;
+        writeln!(f, "{}", self.caret_line)?;
+        writeln!(f, "{}", self.message)
+    }
+}
+
+pub fn build_report<E: Into<Diagnostic>>(src_files: &SourceFiles, error: E) -> Report {
+    build_report_with(src_files, error, CaretStyle::default())
+}
+
+/// Like [`build_report`], but underlines spans in the given style.
+pub fn build_report_with<E: Into<Diagnostic>>(
+    src_files: &SourceFiles,
+    error: E,
+    style: CaretStyle,
+) -> Report {
+    let Diagnostic {
+        error,
+        notes,
+        severity,
+    } = error.into();
     let error_start = error.highlight.start();
     let src_file = src_files.get_by_offset(error_start);
     let line = src_file.get_line(error_start);
-    let highlight = highlight(line.content, line.offset, error.highlight);
+    let highlight = highlight(line.content, line.offset, error.highlight, style);
 
     let line_number_string = line.number.to_string();
     let mut line_number_padding = String::new();
@@ -91,36 +274,52 @@ pub fn __build_report(src_files: &SourceFiles, error: Error) -> [String; 5] {
     }
     let line_number_padding = line_number_padding;
 
-    let mut line0 = src_file.name.clone();
-    line0 += "\n";
+    let file = src_file.name.clone();
 
-    let mut line1 = line_number_padding.clone();
-    line1 += " |\n";
+    let mut gutter = line_number_padding.clone();
+    gutter += " |";
 
-    let mut line2 = line_number_string;
-    line2 += " | ";
-    line2 += line.content;
-    line2 += "\n";
+    let mut source_line = line_number_string;
+    source_line += " | ";
+    source_line += line.content;
 
-    let mut line3 = line_number_padding.clone();
-    line3 += " | ";
-    line3 += &highlight;
-    line3 += "\n";
+    let mut caret_line = line_number_padding;
+    caret_line += " | ";
+    caret_line += &highlight;
 
-    let mut line4 = String::from(error.message);
-    line4 += "\n";
+    let mut message = match severity {
+        Severity::Error => error.message,
+        Severity::Warning => format!("warning: {}", error.message),
+    };
+    for note in notes {
+        message += "\nnote: ";
+        message += &note;
+    }
 
-    [line0, line1, line2, line3, line4]
+    Report {
+        file,
+        gutter,
+        source_line,
+        caret_line,
+        message,
+    }
+}
+
+pub fn __build_report(src_files: &SourceFiles, error: Error) -> [String; 5] {
+    let report = build_report(src_files, error);
+    [
+        report.file + "\n",
+        report.gutter + "\n",
+        report.source_line + "\n",
+        report.caret_line + "\n",
+        report.message + "\n",
+    ]
 }
 
 impl Error {
     pub fn report(self, src_files: &SourceFiles) {
-        let [line0, line1, line2, line3, line4] = __build_report(src_files, self);
-        let _ = io::stdout().write(line0.as_bytes()).unwrap();
-        let _ = io::stdout().write(line1.as_bytes()).unwrap();
-        let _ = io::stdout().write(line2.as_bytes()).unwrap();
-        let _ = io::stdout().write(line3.as_bytes()).unwrap();
-        let _ = io::stdout().write(line4.as_bytes()).unwrap();
+        let report = build_report(src_files, self);
+        let _ = io::stdout().write(report.to_string().as_bytes()).unwrap();
     }
 }
 
@@ -150,6 +349,26 @@ fn test_build_report1() {
     )
 }
 
+#[test]
+fn test_build_report_end_of_input() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(String::from("test"), String::from("x "));
+
+    assert_eq!(
+        __build_report(
+            &src_files,
+            Error {
+                highlight: Highlight::Span(Span {
+                    start: Offset(2),
+                    length: Offset(0)
+                }),
+                message: String::from("Message"),
+            }
+        ),
+        ["test\n", "  |\n", "1 | x \n", "  |   ^\n", "Message\n"]
+    )
+}
+
 #[test]
 fn test_build_report2() {
     let mut src_files = SourceFiles::new();
@@ -180,3 +399,245 @@ fn test_build_report2() {
         ]
     )
 }
+
+#[test]
+fn test_report_display1() {
+    let report = Report {
+        file: String::from("test"),
+        gutter: String::from("  |"),
+        source_line: String::from("1 | this is a line"),
+        caret_line: String::from("  |         ^"),
+        message: String::from("Message"),
+    };
+    assert_eq!(
+        report.to_string(),
+        "test\n  |\n1 | this is a line\n  |         ^\nMessage\n"
+    )
+}
+
+#[test]
+fn test_build_report3() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(
+        String::from("test"),
+        String::from("this is a line\nthis is another line"),
+    );
+
+    assert_eq!(
+        build_report(
+            &src_files,
+            Error {
+                highlight: Highlight::Span(Span {
+                    start: Offset(5),
+                    length: Offset(2)
+                }),
+                message: String::from("Message")
+            }
+        ),
+        Report {
+            file: String::from("test"),
+            gutter: String::from("  |"),
+            source_line: String::from("1 | this is a line"),
+            caret_line: String::from("  |      ^^"),
+            message: String::from("Message"),
+        }
+    )
+}
+
+#[test]
+fn test_build_report_caret_style() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(
+        String::from("test"),
+        String::from("this is a line\nthis is another line"),
+    );
+    let span_error = |length| {
+        Error::at(Highlight::Span(Span {
+            start: Offset(5),
+            length: Offset(length),
+        }))
+        .message("Message")
+    };
+
+    assert_eq!(
+        build_report_with(&src_files, span_error(5), CaretStyle::Ends).caret_line,
+        "  |      ^~~~^"
+    );
+    assert_eq!(
+        build_report_with(&src_files, span_error(5), CaretStyle::Uniform).caret_line,
+        "  |      ^^^^^"
+    );
+    assert_eq!(
+        build_report_with(&src_files, span_error(2), CaretStyle::Ends).caret_line,
+        "  |      ^^"
+    );
+    assert_eq!(
+        build_report_with(&src_files, span_error(1), CaretStyle::Ends).caret_line,
+        build_report_with(&src_files, span_error(1), CaretStyle::Uniform).caret_line
+    );
+}
+
+#[test]
+fn test_build_report_multi1() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(
+        String::from("test"),
+        String::from("(this is a line\nthis is another line"),
+    );
+
+    assert_eq!(
+        __build_report(
+            &src_files,
+            Error {
+                highlight: Highlight::Multi(vec![
+                    Highlight::Point(Offset(0)),
+                    Highlight::Point(Offset(9))
+                ]),
+                message: String::from("Message")
+            }
+        ),
+        [
+            "test\n",
+            "  |\n",
+            "1 | (this is a line\n",
+            "  | ^        ^\n",
+            "Message\n"
+        ]
+    )
+}
+
+#[test]
+fn test_build_report_multi2() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(
+        String::from("test"),
+        String::from("this is a line\nthis is another line"),
+    );
+
+    assert_eq!(
+        __build_report(
+            &src_files,
+            Error {
+                highlight: Highlight::Multi(vec![
+                    Highlight::Point(Offset(20)),
+                    Highlight::Span(Span {
+                        start: Offset(5),
+                        length: Offset(2)
+                    }),
+                ]),
+                message: String::from("Message")
+            }
+        ),
+        [
+            "test\n",
+            "  |\n",
+            "1 | this is a line\n",
+            "  |      ^^\n",
+            "Message\n"
+        ]
+    )
+}
+
+#[test]
+fn test_build_report_builder1() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(
+        String::from("test"),
+        String::from("this is a line\nthis is another line"),
+    );
+
+    let error = Error::at(Highlight::Point(Offset(5)))
+        .message("Message")
+        .note("first note")
+        .note("second note")
+        .severity(Severity::Warning);
+    assert_eq!(
+        build_report(&src_files, error).to_string(),
+        "test\n  |\n1 | this is a line\n  |      ^\nwarning: Message\nnote: first note\nnote: second note\n"
+    )
+}
+
+#[test]
+fn test_build_report_empty_span1() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(
+        String::from("test"),
+        String::from("this is a line\nthis is another line"),
+    );
+
+    assert_eq!(
+        __build_report(
+            &src_files,
+            Error::at(Highlight::Span(Span {
+                start: Offset(5),
+                length: Offset(0)
+            }))
+            .message("Message")
+        ),
+        [
+            "test\n",
+            "  |\n",
+            "1 | this is a line\n",
+            "  |      ^\n",
+            "Message\n"
+        ]
+    )
+}
+
+#[test]
+fn test_build_report_second_file() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(String::from("first"), String::from("one\ntwo\n"));
+    let second_start =
+        src_files.new_source_file(String::from("second"), String::from("abc\nlet x ? y"));
+    assert!(second_start > Offset(0));
+
+    // the '?'
+    let offset = second_start.add(10);
+    assert_eq!(
+        __build_report(
+            &src_files,
+            Error::at(Highlight::Point(offset)).message("Message")
+        ),
+        [
+            "second\n",
+            "  |\n",
+            "2 | let x ? y\n",
+            "  |       ^\n",
+            "Message\n"
+        ]
+    );
+    assert_eq!(
+        __build_report(
+            &src_files,
+            Error::at(Highlight::Span(Span {
+                start: offset,
+                length: Offset(3)
+            }))
+            .message("Message")
+        )[3],
+        "  |       ^^^\n"
+    )
+}
+
+#[test]
+fn test_build_report_end_of_first_file() {
+    let mut src_files = SourceFiles::new();
+    let first_start = src_files.new_source_file(String::from("a"), String::from("(x"));
+    src_files.new_source_file(String::from("b"), String::from("y"));
+
+    // The end of input of the first file is reported against the first file.
+    assert_eq!(
+        __build_report(
+            &src_files,
+            Error::at(Highlight::Point(first_start.add(2))).message("Unexpected end of input")
+        ),
+        [
+            "a\n",
+            "  |\n",
+            "1 | (x\n",
+            "  |   ^\n",
+            "Unexpected end of input\n"
+        ]
+    )
+}
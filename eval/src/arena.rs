@@ -0,0 +1,97 @@
+use std::cell::{Cell, RefCell};
+use std::mem::ManuallyDrop;
+
+/// Like `typed_arena::Arena`, but its memory can be taken back out and reused once the values in
+/// it are no longer needed.
+///
+/// Values are stored in chunks, and a chunk is never pushed to beyond its capacity, so its values
+/// don't move while the arena is alive.
+pub struct Arena<T> {
+    chunks: RefCell<Vec<Vec<T>>>,
+    /// The chunk that values are being allocated in. Any chunks after it are empty.
+    current: Cell<usize>,
+}
+
+impl<T> Arena<T> {
+    pub fn with_capacity(size_items: usize) -> Self {
+        Arena::from_chunks(vec![Vec::with_capacity(size_items.max(1))])
+    }
+
+    /// Allocate in `chunks`, which must be empty, such as those returned by
+    /// [`Arena::into_chunks`].
+    pub fn from_chunks(chunks: Vec<Vec<T>>) -> Self {
+        debug_assert!(chunks.iter().all(|chunk| chunk.is_empty()));
+        let chunks = match chunks.is_empty() {
+            true => vec![Vec::with_capacity(1)],
+            false => chunks,
+        };
+        Arena {
+            chunks: RefCell::new(chunks),
+            current: Cell::new(0),
+        }
+    }
+
+    pub fn alloc(&self, value: T) -> &T {
+        let mut chunks = self.chunks.borrow_mut();
+        let mut current = self.current.get();
+        while chunks[current].len() == chunks[current].capacity() {
+            current += 1;
+            if current == chunks.len() {
+                let capacity = chunks[current - 1].capacity() * 2;
+                chunks.push(Vec::with_capacity(capacity));
+            }
+        }
+        self.current.set(current);
+        let chunk = &mut chunks[current];
+        chunk.push(value);
+        // SAFETY: the chunk had room for `value`, so pushing it didn't move the chunk's values.
+        // They're only dropped when the arena is, and the returned reference borrows the arena.
+        unsafe { &*chunk.as_ptr().add(chunk.len() - 1) }
+    }
+
+    /// Drop the values, and return the emptied chunks for [`Arena::from_chunks`].
+    pub fn into_chunks(self) -> Vec<Vec<T>> {
+        let mut chunks = self.chunks.into_inner();
+        for chunk in chunks.iter_mut() {
+            chunk.clear();
+        }
+        chunks
+    }
+}
+
+/// Change the type of an empty chunk's elements to `U`, keeping its memory.
+///
+/// # Safety
+///
+/// `T` and `U` must have the same size and alignment, such as the same type with different
+/// lifetimes.
+pub unsafe fn cast_empty_chunk<T, U>(chunk: Vec<T>) -> Vec<U> {
+    debug_assert!(chunk.is_empty());
+    let mut chunk = ManuallyDrop::new(chunk);
+    Vec::from_raw_parts(chunk.as_mut_ptr() as *mut U, 0, chunk.capacity())
+}
+
+#[test]
+fn test_arena_chunks() {
+    let arena = Arena::with_capacity(2);
+    let values: Vec<&String> = (0..5).map(|n| arena.alloc(n.to_string())).collect();
+    assert_eq!(values, vec!["0", "1", "2", "3", "4"]);
+    let chunks = arena.into_chunks();
+    assert_eq!(
+        chunks
+            .iter()
+            .map(|chunk| (chunk.len(), chunk.capacity()))
+            .collect::<Vec<_>>(),
+        vec![(0, 2), (0, 4)]
+    );
+
+    // Reusing the chunks doesn't allocate new ones.
+    let arena = Arena::from_chunks(chunks);
+    let first = arena.alloc("5".to_string()) as *const String;
+    for n in 6..10 {
+        let _ = arena.alloc(n.to_string());
+    }
+    let chunks = arena.into_chunks();
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(first, chunks[0].as_ptr());
+}
@@ -0,0 +1,126 @@
+use crate::value::Value;
+use ast::de_bruijn::{Expr, ExprRef};
+use std::collections::HashMap;
+
+/// Remembers the values of closed subexpressions, so that an expression that's shared between
+/// several parts of a program is only evaluated once. Expressions are identified by address, so
+/// only the exact same node is found, and not an equal one elsewhere.
+///
+/// Only closed expressions are cached, because their values don't depend on the environment.
+pub struct EvalCache<'expr, 'value> {
+    values: HashMap<*const Expr<'expr>, &'value Value<'expr, 'value>>,
+    /// For each expression that's been checked, one more than its largest free variable, or 0
+    /// if it's closed. See [`EvalCache::scope`].
+    scopes: HashMap<*const Expr<'expr>, usize>,
+}
+
+impl<'expr, 'value> EvalCache<'expr, 'value> {
+    pub fn new() -> Self {
+        EvalCache {
+            values: HashMap::new(),
+            scopes: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get(&self, expr: ExprRef<'expr>) -> Option<&'value Value<'expr, 'value>> {
+        self.values.get(&(expr as *const Expr)).copied()
+    }
+
+    pub(crate) fn insert(&mut self, expr: *const Expr<'expr>, value: &'value Value<'expr, 'value>) {
+        self.values.insert(expr, value);
+    }
+
+    /// Whether `expr`'s value should be cached. Values and variables are already cheap to
+    /// evaluate, so only closed applications, additions and lets are cached.
+    pub(crate) fn should_cache(&mut self, expr: ExprRef<'expr>) -> bool {
+        match expr {
+            Expr::App(_, _) | Expr::AddU64(_, _) | Expr::Let(_, _) => self.scope(expr) == 0,
+            _ => false,
+        }
+    }
+
+    /// How many binders `expr` needs to be under to be well-scoped: one more than its largest
+    /// free variable, or 0 if it's closed.
+    ///
+    /// Each node's scope is worked out from its children's, and remembered, so checking every
+    /// node of an expression takes time linear in its size. The children are visited with an
+    /// explicit stack, like evaluation, so deep expressions don't overflow the native stack.
+    fn scope(&mut self, expr: ExprRef<'expr>) -> usize {
+        let mut stack = vec![expr];
+        while let Option::Some(&expr) = stack.last() {
+            if self.scopes.contains_key(&(expr as *const Expr)) {
+                stack.pop();
+                continue;
+            }
+            let children: &[ExprRef<'expr>] = match expr {
+                Expr::Var(_) | Expr::U64(_) => &[],
+                Expr::Lam(body) | Expr::Fix(body) => std::slice::from_ref(body),
+                Expr::App(l, r) | Expr::AddU64(l, r) | Expr::Let(l, r) => &[l, r],
+            };
+            let unchecked: Vec<ExprRef<'expr>> = children
+                .iter()
+                .copied()
+                .filter(|child| !self.scopes.contains_key(&(*child as *const Expr)))
+                .collect();
+            if !unchecked.is_empty() {
+                stack.extend(unchecked);
+                continue;
+            }
+            let scope_of = |child: ExprRef<'expr>| self.scopes[&(child as *const Expr)];
+            let scope = match expr {
+                Expr::Var(n) => n.saturating_add(1),
+                Expr::U64(_) => 0,
+                Expr::Lam(body) => scope_of(body).saturating_sub(1),
+                Expr::Fix(body) => scope_of(body),
+                Expr::App(l, r) | Expr::AddU64(l, r) => scope_of(l).max(scope_of(r)),
+                Expr::Let(bound, body) => scope_of(bound).max(scope_of(body).saturating_sub(1)),
+            };
+            self.scopes.insert(expr as *const Expr, scope);
+            stack.pop();
+        }
+        self.scopes[&(expr as *const Expr)]
+    }
+}
+
+impl<'expr, 'value> Default for EvalCache<'expr, 'value> {
+    fn default() -> Self {
+        EvalCache::new()
+    }
+}
+
+#[test]
+fn test_should_cache1() {
+    use ast::de_bruijn::ExprBuilder;
+
+    let builder = ExprBuilder::new();
+    // \x -> (let y = 1 in y + x) + 2
+    let open = builder.mk_let(
+        builder.mk_u64(1),
+        builder.mk_addu64(builder.mk_var(0), builder.mk_var(1)),
+    );
+    let closed = builder.mk_addu64(open, builder.mk_u64(2));
+    let mut cache = EvalCache::new();
+    assert!(!cache.should_cache(open));
+    assert!(!cache.should_cache(closed));
+    assert_eq!(cache.scope(builder.mk_lam(closed)), 0);
+    assert!(cache.should_cache(builder.mk_app(builder.mk_lam(closed), builder.mk_u64(3))));
+}
+
+#[test]
+fn test_should_cache_linear() {
+    use ast::de_bruijn::ExprBuilder;
+
+    // 0 + 1 + ... + n, nested to the left
+    let n = 10_000;
+    let builder = ExprBuilder::new();
+    let mut exprs = vec![builder.mk_u64(0)];
+    for i in 1..=n {
+        exprs.push(builder.mk_addu64(exprs[exprs.len() - 1], builder.mk_u64(i)));
+    }
+    let mut cache = EvalCache::new();
+    // Checking from the root down, as evaluation does, visits each node once.
+    for expr in exprs.iter().rev() {
+        assert_eq!(cache.should_cache(expr), expr != &exprs[0]);
+    }
+    assert_eq!(cache.scopes.len(), 2 * n as usize + 1);
+}
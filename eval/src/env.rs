@@ -0,0 +1,142 @@
+use crate::value::Value;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// The values of the variables in scope, where variable 0 is the most recently bound.
+///
+/// Environments are persistent linked lists. Binding a variable allocates a single node that
+/// shares the rest of the environment, so closures and continuations can hold on to an
+/// environment without it ever being copied.
+///
+/// An environment is only as long as the binders are deeply nested in the program, so it's
+/// dropped recursively like the expression it came from.
+#[derive(Clone)]
+pub struct Env<'expr, 'value> {
+    head: Option<Rc<Node<'expr, 'value>>>,
+}
+
+struct Node<'expr, 'value> {
+    value: &'value Value<'expr, 'value>,
+    next: Env<'expr, 'value>,
+}
+
+pub struct Iter<'env, 'expr, 'value> {
+    current: Option<&'env Node<'expr, 'value>>,
+}
+
+impl<'env, 'expr, 'value> Iterator for Iter<'env, 'expr, 'value> {
+    type Item = &'value Value<'expr, 'value>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.current?;
+        self.current = node.next.head.as_deref();
+        Option::Some(node.value)
+    }
+}
+
+impl<'expr, 'value> Env<'expr, 'value> {
+    pub fn new() -> Self {
+        Env { head: Option::None }
+    }
+
+    /// This environment with `value` bound as variable 0. `self` is shared, not copied.
+    pub fn push(&self, value: &'value Value<'expr, 'value>) -> Self {
+        Env {
+            head: Option::Some(Rc::new(Node {
+                value,
+                next: self.clone(),
+            })),
+        }
+    }
+
+    /// The value of de Bruijn index `ix`, or `None` if the environment binds fewer than `ix + 1`
+    /// variables.
+    pub fn get(&self, ix: usize) -> Option<&'value Value<'expr, 'value>> {
+        self.iter().nth(ix)
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Whether `a` and `b` are the same environment, rather than just equal ones.
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        match (&a.head, &b.head) {
+            (Option::None, Option::None) => true,
+            (Option::Some(a), Option::Some(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// The values in the environment, starting with variable 0.
+    pub fn iter(&self) -> Iter<'_, 'expr, 'value> {
+        Iter {
+            current: self.head.as_deref(),
+        }
+    }
+}
+
+impl<'expr, 'value> Default for Env<'expr, 'value> {
+    fn default() -> Self {
+        Env::new()
+    }
+}
+
+/// Bind the values in order, so the last one is variable 0.
+impl<'expr, 'value> From<&[&'value Value<'expr, 'value>]> for Env<'expr, 'value> {
+    fn from(values: &[&'value Value<'expr, 'value>]) -> Self {
+        values.iter().fold(Env::new(), |env, value| env.push(value))
+    }
+}
+
+impl<'expr, 'value, const N: usize> From<[&'value Value<'expr, 'value>; N]> for Env<'expr, 'value> {
+    fn from(values: [&'value Value<'expr, 'value>; N]) -> Self {
+        Env::from(&values[..])
+    }
+}
+
+impl<'expr, 'value> PartialEq for Env<'expr, 'value> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<'expr, 'value> Eq for Env<'expr, 'value> {}
+
+/// Printed in the order the variables were bound, so variable 0 is last.
+impl<'expr, 'value> Debug for Env<'expr, 'value> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut values = self.iter().collect::<Vec<_>>();
+        values.reverse();
+        values.fmt(formatter)
+    }
+}
+
+#[test]
+fn test_env_get() {
+    let a = &Value::U64(1);
+    let b = &Value::U64(2);
+    let env = Env::new().push(a).push(b);
+    assert_eq!(env.get(0), Option::Some(b));
+    assert_eq!(env.get(1), Option::Some(a));
+    assert_eq!(env.get(2), Option::None);
+    assert_eq!(env.get(usize::MAX), Option::None);
+    assert_eq!(env.len(), 2);
+    assert_eq!(env, Env::from([a, b]));
+}
+
+#[test]
+fn test_env_shared() {
+    let a = &Value::U64(1);
+    let env = Env::new().push(a);
+    let left = env.push(&Value::U64(2));
+    let right = env.push(&Value::U64(3));
+    assert_eq!(left.get(1), Option::Some(a));
+    assert_eq!(right.get(1), Option::Some(a));
+    let tail = |env: &Env<'static, 'static>| env.head.as_ref().unwrap().next.clone();
+    assert!(Env::ptr_eq(&tail(&left), &tail(&right)));
+    assert!(Env::ptr_eq(&tail(&left), &env));
+}
@@ -1,10 +1,30 @@
+use crate::env::Env;
 use crate::value::Value;
 
+use crate::arena::{cast_empty_chunk, Arena};
 use num::Integer;
-use typed_arena::Arena;
+use std::cell::{Cell, RefCell};
+
+/// Integers below this are preallocated by every heap. See [`Heap::u64`].
+const SMALL_U64_LIMIT: u64 = 256;
+
+/// How many values a [`Heap`] has allocated. Preallocated small integers aren't counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeapStats {
+    pub allocations: usize,
+    pub closures: usize,
+    pub u64s: usize,
+    /// Environment nodes allocated by [`Heap::bind`]. These live outside the arena, so they
+    /// aren't included in `allocations`.
+    pub env_nodes: usize,
+}
 
 pub struct Heap<'expr, 'value> {
     arena: Arena<Value<'expr, 'value>>,
+    /// `small_u64s[n]` is `Value::U64(n)`. Integers don't borrow anything, so these can be lent
+    /// out at any lifetime, and the table can be moved to another heap by a [`HeapPool`].
+    small_u64s: Box<[Value<'static, 'static>]>,
+    stats: Cell<HeapStats>,
 }
 
 impl<'expr, 'value> Heap<'expr, 'value> {
@@ -17,6 +37,8 @@ impl<'expr, 'value> Heap<'expr, 'value> {
         };
         Heap {
             arena: Arena::with_capacity(size_items),
+            small_u64s: (0..SMALL_U64_LIMIT).map(Value::U64).collect(),
+            stats: Cell::new(HeapStats::default()),
         }
     }
 
@@ -24,6 +46,199 @@ impl<'expr, 'value> Heap<'expr, 'value> {
     where
         'heap: 'value,
     {
+        let mut stats = self.stats.get();
+        stats.allocations += 1;
+        match val {
+            Value::Closure { .. } => stats.closures += 1,
+            Value::U64(_) => stats.u64s += 1,
+            Value::Fix { .. } => {}
+        }
+        self.stats.set(stats);
         self.arena.alloc(val)
     }
+
+    /// Bind `value` as variable 0 in `env`. This allocates one environment node, which shares
+    /// the rest of `env`.
+    pub fn bind(
+        &self,
+        env: &Env<'expr, 'value>,
+        value: &'value Value<'expr, 'value>,
+    ) -> Env<'expr, 'value> {
+        let mut stats = self.stats.get();
+        stats.env_nodes += 1;
+        self.stats.set(stats);
+        env.push(value)
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        self.stats.get()
+    }
+
+    /// How many `Value::Closure`s have been allocated.
+    pub fn closures_allocated(&self) -> usize {
+        self.stats.get().closures
+    }
+
+    /// How many `Value::U64`s have been allocated, not counting preallocated small integers.
+    pub fn u64s_allocated(&self) -> usize {
+        self.stats.get().u64s
+    }
+
+    /// Get a `Value::U64(n)`. Small integers come from the heap's preallocated table instead of
+    /// being allocated each time.
+    pub fn u64<'heap>(&'heap self, n: u64) -> &'value Value<'expr, 'value>
+    where
+        'heap: 'value,
+    {
+        if n < SMALL_U64_LIMIT {
+            &self.small_u64s[n as usize]
+        } else {
+            self.alloc(Value::U64(n))
+        }
+    }
+}
+
+/// Lends out [`Heap`]s for evaluations that run one after another, such as benchmark iterations,
+/// and reuses their memory.
+///
+/// Values borrow from their heap for as long as it lives, so a heap can't be handed back to the
+/// pool by dropping it. Instead a heap is only lent out for the duration of [`HeapPool::with_heap`],
+/// after which its values are dropped and its arena's memory goes back to the pool.
+pub struct HeapPool {
+    capacity_bytes: usize,
+    heaps: RefCell<Vec<ReturnedHeap>>,
+}
+
+/// What's kept of a heap that's been returned to a [`HeapPool`].
+struct ReturnedHeap {
+    /// The emptied chunks of the heap's arena. They don't contain any values, so their
+    /// lifetimes don't matter.
+    chunks: Vec<Vec<Value<'static, 'static>>>,
+    small_u64s: Box<[Value<'static, 'static>]>,
+}
+
+impl HeapPool {
+    /// A pool whose new heaps each start with `capacity_bytes` of space, as in
+    /// [`Heap::with_capacity`]. Reused heaps start with all the space they ended up with.
+    pub fn new(capacity_bytes: usize) -> Self {
+        HeapPool {
+            capacity_bytes,
+            heaps: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Run `f` with a heap from the pool. Nothing that borrows from the heap can be returned, so
+    /// its memory can be reused once `f` is done. If `f` panics, the heap is dropped instead.
+    ///
+    /// ```
+    /// # use eval::heap::HeapPool;
+    /// # use eval::value::Value;
+    /// let pool = HeapPool::new(1024);
+    /// let n = pool.with_heap(|heap| match heap.u64(1000) {
+    ///     Value::U64(n) => *n,
+    ///     _ => unreachable!(),
+    /// });
+    /// assert_eq!(n, 1000);
+    /// ```
+    ///
+    /// ```compile_fail
+    /// # use eval::heap::HeapPool;
+    /// let pool = HeapPool::new(1024);
+    /// let value = pool.with_heap(|heap| heap.u64(1000));
+    /// ```
+    pub fn with_heap<'expr, R>(
+        &self,
+        f: impl for<'value> FnOnce(&'value Heap<'expr, 'value>) -> R,
+    ) -> R {
+        let heap = match self.heaps.borrow_mut().pop() {
+            Option::None => Heap::with_capacity(self.capacity_bytes),
+            Option::Some(ReturnedHeap { chunks, small_u64s }) => Heap {
+                // Values are covariant in their lifetimes, so no cast is needed in this direction.
+                arena: Arena::from_chunks(chunks),
+                small_u64s,
+                stats: Cell::new(HeapStats::default()),
+            },
+        };
+        // SAFETY: `f` works for any `'value`, and `R` doesn't depend on `'value`, so nothing
+        // borrowed from the heap outlives this call. That means the heap isn't borrowed once `f`
+        // returns, even though the borrow checker can't tell, and it's safe to take it apart.
+        let result = f(unsafe { &*(&heap as *const Heap) });
+        let small_u64s = heap.small_u64s;
+        let chunks = heap
+            .arena
+            .into_chunks()
+            .into_iter()
+            // SAFETY: the chunks are empty, and only the lifetimes of their element type change.
+            .map(|chunk| unsafe { cast_empty_chunk(chunk) })
+            .collect();
+        self.heaps
+            .borrow_mut()
+            .push(ReturnedHeap { chunks, small_u64s });
+        result
+    }
+}
+
+#[test]
+fn test_heap_pool1() {
+    let pool = HeapPool::new(1024);
+    for _ in 0..2 {
+        pool.with_heap(|heap| {
+            let small = heap.u64(1);
+            let large = heap.u64(1000);
+            let closure = heap.alloc(Value::Closure {
+                env: Env::from([small, large]),
+                body: &ast::de_bruijn::Expr::Var(0),
+            });
+            assert_eq!(small, &Value::U64(1));
+            assert_eq!(large, &Value::U64(1000));
+            match closure {
+                Value::Closure { env, .. } => assert_eq!(env, &Env::from([small, large])),
+                _ => panic!("expected a closure"),
+            }
+            assert_eq!(heap.stats().allocations, 2);
+        })
+    }
+}
+
+#[test]
+fn test_heap_pool_reuse() {
+    let pool = HeapPool::new(0);
+    let first = pool.with_heap(|heap| {
+        for n in 1000..1100 {
+            let _ = heap.u64(n);
+        }
+        (
+            heap.u64(1100) as *const Value as usize,
+            heap.u64(1) as *const Value as usize,
+        )
+    });
+    assert_eq!(pool.heaps.borrow().len(), 1);
+    let chunks = pool.heaps.borrow()[0].chunks.len();
+
+    // The second heap allocates in the first heap's memory, which is already big enough.
+    let second = pool.with_heap(|heap| {
+        for n in 2000..2100 {
+            assert_eq!(heap.u64(n), &Value::U64(n));
+        }
+        assert_eq!(heap.stats().u64s, 100);
+        (
+            heap.u64(2100) as *const Value as usize,
+            heap.u64(1) as *const Value as usize,
+        )
+    });
+    // The small integers are reused too.
+    assert_eq!(first, second);
+    assert_eq!(pool.heaps.borrow().len(), 1);
+    assert_eq!(pool.heaps.borrow()[0].chunks.len(), chunks);
+}
+
+#[test]
+fn test_heap_small_u64s() {
+    let first = Heap::with_capacity(1024);
+    let second = Heap::with_capacity(1024);
+    // Each heap has its own table of small integers.
+    assert!(std::ptr::eq(first.u64(255), first.u64(255)));
+    assert!(!std::ptr::eq(first.u64(255), second.u64(255)));
+    assert!(!std::ptr::eq(first.u64(256), first.u64(256)));
+    assert_eq!(first.stats().u64s, 2);
 }
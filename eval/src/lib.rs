@@ -1,59 +1,140 @@
+mod arena;
+pub mod cache;
+pub mod env;
 pub mod heap;
+pub mod prim;
 pub mod stack;
 pub mod value;
 
+use crate::cache::EvalCache;
+use crate::env::Env;
 use crate::heap::Heap;
 use crate::value::Value;
-use ast::de_bruijn::{Expr, ExprRef};
+use ast::de_bruijn::{Expr, ExprBuilder, ExprRef};
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum EvalError {
+    /// A variable referred to a binder that doesn't exist, because the expression isn't well
+    /// scoped in its environment.
+    UnboundIndex(usize),
+    /// An arithmetic result didn't fit in a `u64`.
+    Overflow,
+    /// A function was found where a number was needed: an operand of an addition, or the result
+    /// of [`observe`].
+    NotANumber,
+    /// A number was applied to an argument, or a fixpoint's body wasn't a lambda.
+    NotAFunction,
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EvalError::UnboundIndex(ix) => write!(formatter, "Unbound variable #{}", ix),
+            EvalError::Overflow => formatter.write_str("Arithmetic overflow"),
+            EvalError::NotANumber => formatter.write_str("Expected a number, but got a function"),
+            EvalError::NotAFunction => formatter.write_str("Expected a function, but got a number"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Evaluate `expr` in `env`, where `env`'s last element is variable 0. This uses the same
+/// explicit continuation stack as [`eval_loop`], so deeply nested expressions don't overflow the
+/// native stack.
 pub fn eval<'expr, 'heap, 'value>(
     heap: &'heap Heap<'expr, 'value>,
-    env: &Vec<&'value Value<'expr, 'value>>,
+    env: &[&'value Value<'expr, 'value>],
     expr: ExprRef<'expr>,
 ) -> &'value Value<'expr, 'value>
 where
     'heap: 'value,
 {
-    let res = match expr {
-        Expr::Var(n) => env[env.len() - n - 1],
-        Expr::App(l, r) => {
-            let l_value = eval(heap, env, l);
-            match l_value {
-                Value::Closure { env: next, body } => {
-                    let r_value = eval(heap, env, r);
-
-                    let mut env = next.clone();
-                    env.push(r_value);
-                    let res = eval(heap, &env, body);
-                    res
-                }
-                _ => panic!("eval failed: expected Closure, got {:?}", l_value),
-            }
+    match try_eval(heap, env, expr) {
+        Result::Ok(value) => value,
+        Result::Err(err) => panic!("eval failed: {:?}", err),
+    }
+}
+
+/// Like [`eval`], but returns an error instead of panicking when a variable is unbound or an
+/// addition overflows.
+pub fn try_eval<'expr, 'heap, 'value>(
+    heap: &'heap Heap<'expr, 'value>,
+    env: &[&'value Value<'expr, 'value>],
+    expr: ExprRef<'expr>,
+) -> Result<&'value Value<'expr, 'value>, EvalError>
+where
+    'heap: 'value,
+{
+    match __eval_loop(
+        heap,
+        Option::None,
+        Env::from(env),
+        expr,
+        Option::None,
+        &mut 0,
+    )? {
+        Option::Some(value) => Result::Ok(value),
+        Option::None => panic!("eval failed: ran out of fuel"),
+    }
+}
+
+/// Turn a value back into an expression that evaluates to it. The variables that a closure
+/// captures are replaced by the reified values from its environment, so the result is closed.
+/// Closure bodies aren't evaluated, so the result isn't necessarily in normal form.
+pub fn reify<'builder, 'expr, 'value>(
+    builder: &'builder ExprBuilder<'expr>,
+    value: &Value<'expr, 'value>,
+) -> ExprRef<'expr>
+where
+    'builder: 'expr,
+{
+    match value {
+        Value::U64(n) => builder.mk_u64(*n),
+        Value::Closure { env, body } => builder.mk_lam(__reify_body(builder, env, 1, body)),
+        Value::Fix { env, body } => {
+            builder.mk_fix(builder.mk_lam(__reify_body(builder, env, 1, body)))
         }
-        Expr::Lam(body) => heap.alloc(Value::Closure {
-            env: env.clone(),
-            body: body,
-        }),
-        Expr::U64(n) => heap.alloc(Value::U64(*n)),
-        Expr::AddU64(l, r) => {
-            let lvalue = eval(heap, env, l);
-            match lvalue {
-                Value::U64(l_n) => {
-                    let rvalue = eval(heap, env, r);
-
-                    match rvalue {
-                        Value::U64(r_n) => heap.alloc(Value::U64(l_n + r_n)),
-                        r_value => panic!("eval failed: expected U64, got {:?}", r_value),
-                    }
-                }
-                l_value => panic!("eval failed: expected U64, got {:?}", l_value),
+    }
+}
+
+/// Reify the body of a closure. `depth` is the number of variables bound between the closure's
+/// environment and `expr`.
+fn __reify_body<'builder, 'expr, 'value>(
+    builder: &'builder ExprBuilder<'expr>,
+    env: &Env<'expr, 'value>,
+    depth: usize,
+    expr: ExprRef<'expr>,
+) -> ExprRef<'expr>
+where
+    'builder: 'expr,
+{
+    match expr {
+        Expr::Var(n) => {
+            if *n < depth {
+                builder.mk_var(*n)
+            } else {
+                reify(builder, env.get(n - depth).unwrap())
             }
         }
-    };
-    res
+        Expr::Lam(body) => builder.mk_lam(__reify_body(builder, env, depth + 1, body)),
+        Expr::App(l, r) => builder.mk_app(
+            __reify_body(builder, env, depth, l),
+            __reify_body(builder, env, depth, r),
+        ),
+        Expr::U64(n) => builder.mk_u64(*n),
+        Expr::AddU64(l, r) => builder.mk_addu64(
+            __reify_body(builder, env, depth, l),
+            __reify_body(builder, env, depth, r),
+        ),
+        Expr::Let(bound, body) => builder.mk_let(
+            __reify_body(builder, env, depth, bound),
+            __reify_body(builder, env, depth + 1, body),
+        ),
+        Expr::Fix(body) => builder.mk_fix(__reify_body(builder, env, depth, body)),
+    }
 }
 
-type Env<'expr, 'value> = Vec<&'value Value<'expr, 'value>>;
 type ValueRef<'expr, 'value> = &'value Value<'expr, 'value>;
 
 #[derive(Debug)]
@@ -64,10 +145,15 @@ enum Hole {
 /// The meaning of `Cont` is a function from `ValueRef -> ValueRef`
 #[derive(Debug)]
 enum Cont<'expr, 'value> {
-    AppL(Env<'expr, 'value>, Hole, ExprRef<'expr>),
+    /// Apply the function in the hole to each of these arguments in turn. The arguments are in
+    /// reverse order, so the next one is at the end. There's always at least one.
+    AppArgs(Env<'expr, 'value>, Hole, Vec<ExprRef<'expr>>),
     AppR(Env<'expr, 'value>, ExprRef<'expr>, Hole),
     AddU64L(Env<'expr, 'value>, Hole, ExprRef<'expr>),
     AddU64R(u64, Hole),
+    LetBody(Env<'expr, 'value>, Hole, ExprRef<'expr>),
+    /// Cache the value as the value of this expression.
+    Memo(*const Expr<'expr>, Hole),
 }
 
 #[derive(Debug)]
@@ -80,6 +166,163 @@ pub fn eval_loop<'expr, 'heap, 'value>(
     heap: &'heap Heap<'expr, 'value>,
     expr: ExprRef<'expr>,
 ) -> ValueRef<'expr, 'value>
+where
+    'heap: 'value,
+{
+    match try_eval_loop(heap, expr) {
+        Result::Ok(value) => value,
+        Result::Err(err) => panic!("eval_loop failed: {:?}", err),
+    }
+}
+
+/// Like [`eval_loop`], but returns an error instead of panicking when a variable is unbound or an
+/// addition overflows.
+pub fn try_eval_loop<'expr, 'heap, 'value>(
+    heap: &'heap Heap<'expr, 'value>,
+    expr: ExprRef<'expr>,
+) -> Result<ValueRef<'expr, 'value>, EvalError>
+where
+    'heap: 'value,
+{
+    match __eval_loop(heap, Option::None, Env::new(), expr, Option::None, &mut 0)? {
+        Option::Some(value) => Result::Ok(value),
+        Option::None => panic!("eval_loop failed: ran out of fuel"),
+    }
+}
+
+/// Evaluate a closed expression that should produce a number, and get the number.
+pub fn observe<'expr, 'heap, 'value>(
+    heap: &'heap Heap<'expr, 'value>,
+    expr: ExprRef<'expr>,
+) -> Result<u64, EvalError>
+where
+    'heap: 'value,
+{
+    match try_eval_loop(heap, expr)? {
+        Value::U64(n) => Result::Ok(*n),
+        Value::Closure { .. } | Value::Fix { .. } => Result::Err(EvalError::NotANumber),
+    }
+}
+
+/// Like [`eval_loop`], but looks up closed subexpressions in `cache` before evaluating them,
+/// and adds their values to it afterwards.
+pub fn eval_loop_cached<'expr, 'heap, 'value>(
+    heap: &'heap Heap<'expr, 'value>,
+    cache: &mut EvalCache<'expr, 'value>,
+    expr: ExprRef<'expr>,
+) -> ValueRef<'expr, 'value>
+where
+    'heap: 'value,
+{
+    match __eval_loop(
+        heap,
+        Option::Some(cache),
+        Env::new(),
+        expr,
+        Option::None,
+        &mut 0,
+    ) {
+        Result::Ok(Option::Some(value)) => value,
+        Result::Ok(Option::None) => panic!("eval_loop failed: ran out of fuel"),
+        Result::Err(err) => panic!("eval_loop failed: {:?}", err),
+    }
+}
+
+/// Like [`eval_loop`], but also counts the beta reductions, which is the number of times a
+/// closure's body is entered with an argument.
+pub fn eval_loop_counted<'expr, 'heap, 'value>(
+    heap: &'heap Heap<'expr, 'value>,
+    expr: ExprRef<'expr>,
+) -> (ValueRef<'expr, 'value>, u64)
+where
+    'heap: 'value,
+{
+    let mut reductions = 0;
+    match __eval_loop(
+        heap,
+        Option::None,
+        Env::new(),
+        expr,
+        Option::None,
+        &mut reductions,
+    ) {
+        Result::Ok(Option::Some(value)) => (value, reductions),
+        Result::Ok(Option::None) => panic!("eval_loop failed: ran out of fuel"),
+        Result::Err(err) => panic!("eval_loop failed: {:?}", err),
+    }
+}
+
+/// Like `eval_loop`, but gives up and returns `None` after `fuel` steps.
+pub fn eval_loop_with_fuel<'expr, 'heap, 'value>(
+    heap: &'heap Heap<'expr, 'value>,
+    expr: ExprRef<'expr>,
+    fuel: usize,
+) -> Option<ValueRef<'expr, 'value>>
+where
+    'heap: 'value,
+{
+    match try_eval_loop_with_fuel(heap, expr, fuel) {
+        Result::Ok(value) => value,
+        Result::Err(err) => panic!("eval_loop failed: {:?}", err),
+    }
+}
+
+/// Like [`eval_loop_with_fuel`], but returns an error instead of panicking when evaluation goes
+/// wrong, such as when a number is applied to an argument.
+pub fn try_eval_loop_with_fuel<'expr, 'heap, 'value>(
+    heap: &'heap Heap<'expr, 'value>,
+    expr: ExprRef<'expr>,
+    fuel: usize,
+) -> Result<Option<ValueRef<'expr, 'value>>, EvalError>
+where
+    'heap: 'value,
+{
+    __eval_loop(
+        heap,
+        Option::None,
+        Env::new(),
+        expr,
+        Option::Some(fuel),
+        &mut 0,
+    )
+}
+
+/// Split a left-nested application into its head and its arguments, last argument first. A
+/// saturated addition (see [`saturated_add`]) is kept whole as the head, so that it's still
+/// recognized.
+fn app_spine<'expr>(expr: ExprRef<'expr>) -> (ExprRef<'expr>, Vec<ExprRef<'expr>>) {
+    let mut head = expr;
+    let mut args = Vec::new();
+    while let Expr::App(l, r) = head {
+        if saturated_add(head).is_some() {
+            break;
+        }
+        args.push(*r);
+        head = l;
+    }
+    (head, args)
+}
+
+/// If `expr` applies `\a -> \b -> a + b` (or `b + a`) to two arguments, get the arguments.
+/// The evaluator adds them directly instead of building the intermediate closures.
+fn saturated_add<'expr>(expr: ExprRef<'expr>) -> Option<(ExprRef<'expr>, ExprRef<'expr>)> {
+    match expr {
+        Expr::App(Expr::App(Expr::Lam(Expr::Lam(Expr::AddU64(l, r))), x), y) => match (l, r) {
+            (Expr::Var(1), Expr::Var(0)) | (Expr::Var(0), Expr::Var(1)) => Option::Some((x, y)),
+            _ => Option::None,
+        },
+        _ => Option::None,
+    }
+}
+
+fn __eval_loop<'expr, 'heap, 'value>(
+    heap: &'heap Heap<'expr, 'value>,
+    mut cache: Option<&mut EvalCache<'expr, 'value>>,
+    mut env: Env<'expr, 'value>,
+    expr: ExprRef<'expr>,
+    mut fuel: Option<usize>,
+    reductions: &mut u64,
+) -> Result<Option<ValueRef<'expr, 'value>>, EvalError>
 where
     'heap: 'value,
 {
@@ -87,58 +330,113 @@ where
     use crate::Cont::*;
     use crate::Hole::*;
 
-    let mut env: Env<'expr, 'value> = Vec::new();
     let mut code: Code<'expr, 'value> = Input(expr);
     let mut cont: Vec<Cont<'expr, 'value>> = Vec::new();
     loop {
+        if let Option::Some(remaining) = fuel {
+            if remaining == 0 {
+                return Result::Ok(Option::None);
+            }
+            fuel = Option::Some(remaining - 1);
+        }
         // println!("C: {:?}", code);
         // println!("E: {:?}", env);
         // println!("K: {:?}", cont);
         // println!("---------------------------------");
         match code {
-            Input(expr) => match expr {
-                Expr::U64(n) => {
-                    code = Output(heap.alloc(Value::U64(*n)));
-                }
-                Expr::Var(n) => {
-                    code = Output(env[env.len() - n - 1]);
-                }
-                Expr::App(l, r) => {
-                    code = Input(l);
-                    cont.push(AppL(env.clone(), Hole, r));
-                }
-                Expr::Lam(body) => {
-                    code = Output(heap.alloc(Value::Closure {
-                        env: env.clone(),
-                        body: body,
-                    }));
+            Input(expr) => {
+                if let Option::Some(cache) = cache.as_deref_mut() {
+                    if let Option::Some(value) = cache.get(expr) {
+                        code = Output(value);
+                        continue;
+                    }
+                    if cache.should_cache(expr) {
+                        cont.push(Memo(expr, Hole));
+                    }
                 }
-                Expr::AddU64(l, r) => {
-                    code = Input(l);
-                    cont.push(AddU64L(env.clone(), Hole, r));
+                match expr {
+                    Expr::U64(n) => {
+                        code = Output(heap.u64(*n));
+                    }
+                    Expr::Var(n) => {
+                        let value = env.get(*n).ok_or(EvalError::UnboundIndex(*n))?;
+                        code = Output(value);
+                    }
+                    Expr::App(_, _) => match saturated_add(expr) {
+                        Option::Some((x, y)) => {
+                            // The two closures aren't built, but they're still counted as
+                            // reductions, so the count doesn't depend on how the addition is
+                            // written.
+                            *reductions += 2;
+                            code = Input(x);
+                            cont.push(AddU64L(env.clone(), Hole, y));
+                        }
+                        Option::None => {
+                            // The head is evaluated once, and then applied to the arguments
+                            // one at a time by a single `AppArgs` frame.
+                            let (head, args) = app_spine(expr);
+                            code = Input(head);
+                            cont.push(AppArgs(env.clone(), Hole, args));
+                        }
+                    },
+                    Expr::Lam(body) => {
+                        code = Output(heap.alloc(Value::Closure {
+                            env: env.clone(),
+                            body: body,
+                        }));
+                    }
+                    Expr::AddU64(l, r) => {
+                        code = Input(l);
+                        cont.push(AddU64L(env.clone(), Hole, r));
+                    }
+                    Expr::Let(bound, body) => {
+                        code = Input(bound);
+                        cont.push(LetBody(env.clone(), Hole, body));
+                    }
+                    Expr::Fix(body) => match body {
+                        Expr::Lam(body) => {
+                            code = Output(heap.alloc(Value::Fix {
+                                env: env.clone(),
+                                body,
+                            }));
+                        }
+                        _ => return Result::Err(EvalError::NotAFunction),
+                    },
                 }
-            },
+            }
             Output(value) => match cont.pop() {
                 Option::None => match code {
                     Input(_) => panic!("eval_loop failed: no output to return"),
                     Output(value) => {
-                        return value;
+                        return Result::Ok(Option::Some(value));
                     }
                 },
                 Option::Some(c) => match c {
-                    AppL(r_env, Hole, r) => match value {
+                    AppArgs(args_env, Hole, mut args) => match value {
                         Value::Closure { env: l_env, body } => {
-                            code = Input(r);
-                            env = r_env;
+                            let arg = match args.pop() {
+                                Option::Some(arg) => arg,
+                                Option::None => panic!("eval_loop failed: no arguments to apply"),
+                            };
+                            code = Input(arg);
+                            if args.is_empty() {
+                                env = args_env;
+                            } else {
+                                env = args_env.clone();
+                                cont.push(AppArgs(args_env, Hole, args));
+                            }
                             cont.push(AppR(l_env.clone(), body, Hole));
                         }
-                        _ => panic!("eval_loop failed: Expected closure, got {:?}", value),
+                        Value::Fix { env: f_env, body } => {
+                            code = Input(body);
+                            env = heap.bind(f_env, value);
+                            cont.push(AppArgs(args_env, Hole, args));
+                        }
+                        Value::U64(_) => return Result::Err(EvalError::NotAFunction),
                     },
                     AppR(next_env, body, Hole) => {
-                        let mut next_env = next_env;
-                        next_env.push(value);
-
-                        env = next_env;
+                        *reductions += 1;
+                        env = heap.bind(&next_env, value);
                         code = Input(body);
                     }
                     AddU64L(r_env, Hole, r) => match value {
@@ -147,14 +445,24 @@ where
                             env = r_env;
                             cont.push(AddU64R(*l, Hole));
                         }
-                        _ => panic!("eval_loop failed: Expected u64, got {:?}", value),
+                        _ => return Result::Err(EvalError::NotANumber),
                     },
                     AddU64R(l, Hole) => match value {
                         Value::U64(r) => {
-                            code = Output(heap.alloc(Value::U64(l + r)));
+                            code = Output(heap.u64(prim::add(l, *r)?));
                         }
-                        _ => panic!("eval_loop failed: Expected u64, got {:?}", value),
+                        _ => return Result::Err(EvalError::NotANumber),
                     },
+                    Memo(expr, Hole) => {
+                        if let Option::Some(cache) = cache.as_deref_mut() {
+                            cache.insert(expr, value);
+                        }
+                        code = Output(value);
+                    }
+                    LetBody(body_env, Hole, body) => {
+                        env = heap.bind(&body_env, value);
+                        code = Input(body);
+                    }
                 },
             },
         }
@@ -165,7 +473,7 @@ where
 fn test_eval1() {
     let input = &Expr::Lam(&Expr::Var(0));
     let output = &Value::Closure {
-        env: Vec::new(),
+        env: Env::new(),
         body: &Expr::Var(0),
     };
     let mut heap = Heap::with_capacity(1024);
@@ -177,7 +485,7 @@ fn test_eval2() {
     let id = &Expr::Lam(&Expr::Var(0));
     let input = &Expr::App(id, id);
     let output = &Value::Closure {
-        env: Vec::new(),
+        env: Env::new(),
         body: &Expr::Var(0),
     };
     let mut heap = Heap::with_capacity(1024);
@@ -188,13 +496,13 @@ fn test_eval2() {
 fn test_eval3() {
     let id = &Expr::Lam(&Expr::Var(0));
     let id_value = &Value::Closure {
-        env: Vec::new(),
+        env: Env::new(),
         body: &Expr::Var(0),
     };
     let konst = &Expr::Lam(&Expr::Lam(&Expr::Var(1)));
     let input = &Expr::App(konst, id);
     let output = &Value::Closure {
-        env: vec![id_value],
+        env: Env::from([id_value]),
         body: &Expr::Var(1),
     };
     let mut heap = Heap::with_capacity(1024);
@@ -208,7 +516,7 @@ fn test_eval4() {
     let konst_id = &Expr::App(konst, id);
     let input = &Expr::App(konst_id, konst);
     let output = &Value::Closure {
-        env: Vec::new(),
+        env: Env::new(),
         body: &Expr::Var(0),
     };
     let mut heap = Heap::with_capacity(1024);
@@ -238,11 +546,206 @@ fn test_eval6() {
     assert_eq!(eval(&mut heap, &Vec::new(), input), output)
 }
 
+#[test]
+fn test_eval_small_u64_shared() {
+    let input = &Expr::AddU64(&Expr::U64(0), &Expr::U64(0));
+    let heap = Heap::with_capacity(1024);
+    let first = eval(&heap, &Vec::new(), input);
+    let second = eval(&heap, &Vec::new(), input);
+    assert_eq!(first, &Value::U64(0));
+    assert!(std::ptr::eq(first, second))
+}
+
+#[test]
+fn test_eval_deep_spine() {
+    // id id id ... id, nested to the left
+    let builder = ExprBuilder::new();
+    let id = builder.mk_lam(builder.mk_var(0));
+    let mut input = id;
+    for _ in 0..10_000 {
+        input = builder.mk_app(input, id);
+    }
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(
+        eval(&heap, &Vec::new(), input),
+        &Value::Closure {
+            env: Env::new(),
+            body: &Expr::Var(0),
+        }
+    )
+}
+
+#[test]
+fn test_eval_loop_spine_steps() {
+    // (\x1 -> ... \xn -> x1) 0 ... (n - 1)
+    let n = 1000;
+    let builder = ExprBuilder::new();
+    let mut head = builder.mk_var(n - 1);
+    for _ in 0..n {
+        head = builder.mk_lam(head);
+    }
+    let mut input = head;
+    for i in 0..n {
+        input = builder.mk_app(input, builder.mk_u64(i as u64));
+    }
+
+    // The spine is entered once, then each argument takes 4 steps: evaluating it, its value,
+    // entering the closure's body, and the body's value.
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(
+        eval_loop_with_fuel(&heap, input, 4 * n + 3),
+        Option::Some(&Value::U64(0))
+    );
+    assert_eq!(heap.closures_allocated(), n);
+    // the arguments below 256 are shared
+    assert_eq!(heap.u64s_allocated(), n - 256);
+}
+
+#[test]
+fn test_eval_loop_counted() {
+    // (\x -> x) (\y -> y)
+    let id = &Expr::Lam(&Expr::Var(0));
+    let input = &Expr::App(id, id);
+    let heap = Heap::with_capacity(1024);
+    let (value, reductions) = eval_loop_counted(&heap, input);
+    assert_eq!(
+        value,
+        &Value::Closure {
+            env: Env::new(),
+            body: &Expr::Var(0),
+        }
+    );
+    assert_eq!(reductions, 1);
+
+    // (\f -> f 9 7) (\a -> \b -> a + b) applies three closures
+    let plus = &Expr::Lam(&Expr::Lam(&Expr::AddU64(&Expr::Var(1), &Expr::Var(0))));
+    let apply_9_7 = &Expr::Lam(&Expr::App(
+        &Expr::App(&Expr::Var(0), &Expr::U64(9)),
+        &Expr::U64(7),
+    ));
+    let input = &Expr::App(apply_9_7, plus);
+    let heap = Heap::with_capacity(1024);
+    let (value, reductions) = eval_loop_counted(&heap, input);
+    assert_eq!(value, &Value::U64(16));
+    assert_eq!(reductions, 3);
+
+    // (\a -> \b -> a + b) 1 2 is a saturated addition, but it's still two reductions
+    let input = &Expr::App(&Expr::App(plus, &Expr::U64(1)), &Expr::U64(2));
+    let heap = Heap::with_capacity(1024);
+    let (value, reductions) = eval_loop_counted(&heap, input);
+    assert_eq!(value, &Value::U64(3));
+    assert_eq!(reductions, 2);
+}
+
+#[test]
+fn test_try_eval_overflow() {
+    let input = &Expr::AddU64(&Expr::U64(u64::MAX), &Expr::U64(1));
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(
+        try_eval(&heap, &Vec::new(), input),
+        Result::Err(EvalError::Overflow)
+    );
+    assert_eq!(
+        try_eval_loop(&heap, input),
+        Result::Err(EvalError::Overflow)
+    )
+}
+
+#[test]
+fn test_observe() {
+    let plus = &Expr::Lam(&Expr::Lam(&Expr::AddU64(&Expr::Var(0), &Expr::Var(1))));
+    let input = &Expr::App(&Expr::App(plus, &Expr::U64(9)), &Expr::U64(7));
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(observe(&heap, input), Result::Ok(16));
+    assert_eq!(
+        observe(&heap, &Expr::App(plus, &Expr::U64(9))),
+        Result::Err(EvalError::NotANumber)
+    )
+}
+
+#[test]
+fn test_try_eval_loop_with_fuel_type_errors() {
+    let id = &Expr::Lam(&Expr::Var(0));
+    let apply_number = &Expr::App(&Expr::U64(1), id);
+    let add_function_l = &Expr::AddU64(id, &Expr::U64(1));
+    let add_function_r = &Expr::AddU64(&Expr::U64(1), id);
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(
+        try_eval_loop_with_fuel(&heap, apply_number, 100),
+        Result::Err(EvalError::NotAFunction)
+    );
+    assert_eq!(
+        try_eval_loop_with_fuel(&heap, add_function_l, 100),
+        Result::Err(EvalError::NotANumber)
+    );
+    assert_eq!(
+        try_eval_loop_with_fuel(&heap, add_function_r, 100),
+        Result::Err(EvalError::NotANumber)
+    )
+}
+
+#[test]
+fn test_try_eval_loop_fix_not_lambda() {
+    let builder = ExprBuilder::new();
+    let input = builder.mk_fix(builder.mk_u64(1));
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(
+        try_eval_loop(&heap, input),
+        Result::Err(EvalError::NotAFunction)
+    );
+    assert_eq!(
+        try_eval_loop_with_fuel(&heap, input, 100),
+        Result::Err(EvalError::NotAFunction)
+    );
+    assert_eq!(
+        try_eval(&heap, &[], input),
+        Result::Err(EvalError::NotAFunction)
+    )
+}
+
+#[test]
+fn test_eval_error_boxed() {
+    let input = &Expr::AddU64(&Expr::U64(u64::MAX), &Expr::U64(1));
+    let heap = Heap::with_capacity(1024);
+    let err: Box<dyn std::error::Error> = try_eval_loop(&heap, input).unwrap_err().into();
+    assert_eq!(err.to_string(), "Arithmetic overflow");
+
+    let err: Box<dyn std::error::Error> = Box::new(EvalError::UnboundIndex(2));
+    assert_eq!(err.to_string(), "Unbound variable #2")
+}
+
+#[test]
+fn test_try_eval_unbound() {
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(
+        try_eval(&heap, &Vec::new(), &Expr::Var(0)),
+        Result::Err(EvalError::UnboundIndex(0))
+    );
+    assert_eq!(
+        try_eval_loop(&heap, &Expr::App(&Expr::Lam(&Expr::Var(1)), &Expr::U64(0))),
+        Result::Err(EvalError::UnboundIndex(1))
+    )
+}
+
+#[test]
+fn test_try_eval_unbound_max() {
+    let heap = Heap::with_capacity(1024);
+    let one = heap.u64(1);
+    assert_eq!(
+        try_eval_loop(&heap, &Expr::Var(usize::MAX)),
+        Result::Err(EvalError::UnboundIndex(usize::MAX))
+    );
+    assert_eq!(
+        try_eval(&heap, &[one], &Expr::Var(usize::MAX)),
+        Result::Err(EvalError::UnboundIndex(usize::MAX))
+    )
+}
+
 #[test]
 fn test_eval_loop1() {
     let input = &Expr::Lam(&Expr::Var(0));
     let output = &Value::Closure {
-        env: Vec::new(),
+        env: Env::new(),
         body: &Expr::Var(0),
     };
     let mut heap = Heap::with_capacity(1024);
@@ -254,7 +757,7 @@ fn test_eval_loop2() {
     let id = &Expr::Lam(&Expr::Var(0));
     let input = &Expr::App(id, id);
     let output = &Value::Closure {
-        env: Vec::new(),
+        env: Env::new(),
         body: &Expr::Var(0),
     };
     let mut heap = Heap::with_capacity(1024);
@@ -265,13 +768,13 @@ fn test_eval_loop2() {
 fn test_eval_loop3() {
     let id = &Expr::Lam(&Expr::Var(0));
     let id_value = &Value::Closure {
-        env: Vec::new(),
+        env: Env::new(),
         body: &Expr::Var(0),
     };
     let konst = &Expr::Lam(&Expr::Lam(&Expr::Var(1)));
     let input = &Expr::App(konst, id);
     let output = &Value::Closure {
-        env: vec![id_value],
+        env: Env::from([id_value]),
         body: &Expr::Var(1),
     };
     let mut heap = Heap::with_capacity(1024);
@@ -285,21 +788,51 @@ fn test_eval_loop4() {
     let konst_id = &Expr::App(konst, id);
     let input = &Expr::App(konst_id, konst);
     let output = &Value::Closure {
-        env: Vec::new(),
+        env: Env::new(),
         body: &Expr::Var(0),
     };
     let mut heap = Heap::with_capacity(1024);
     assert_eq!(eval_loop(&mut heap, input), output)
 }
 
+/// Evaluate `expr` with [`eval_loop`] and check that the result is the number `n`.
+#[cfg(test)]
+fn assert_evaluates_to(expr: ExprRef, n: u64) {
+    let heap = Heap::with_capacity(1024);
+    match eval_loop(&heap, expr) {
+        Value::U64(m) => assert_eq!(*m, n, "expected {:?} to evaluate to {}", expr, n),
+        value => panic!(
+            "expected {:?} to evaluate to {}, but got a function: {:?}",
+            expr, n, value
+        ),
+    }
+}
+
 #[test]
 fn test_eval_loop5() {
     let plus = &Expr::Lam(&Expr::Lam(&Expr::AddU64(&Expr::Var(0), &Expr::Var(1))));
     let plus_9 = &Expr::App(plus, &Expr::U64(9));
     let input = &Expr::App(plus_9, &Expr::U64(7));
-    let output = &Value::U64(16);
-    let mut heap = Heap::with_capacity(1024);
-    assert_eq!(eval_loop(&mut heap, input), output)
+    assert_evaluates_to(input, 16)
+}
+
+#[test]
+fn test_eval_loop5_allocations() {
+    let plus = &Expr::Lam(&Expr::Lam(&Expr::AddU64(&Expr::Var(0), &Expr::Var(1))));
+
+    // `plus 9 7` is a saturated addition of small numbers, so nothing is allocated
+    let input = &Expr::App(&Expr::App(plus, &Expr::U64(9)), &Expr::U64(7));
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(eval_loop(&heap, input), &Value::U64(16));
+    assert_eq!(heap.closures_allocated(), 0);
+    assert_eq!(heap.u64s_allocated(), 0);
+
+    // the two arguments and the result are too big to be shared
+    let input = &Expr::App(&Expr::App(plus, &Expr::U64(9000)), &Expr::U64(7000));
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(eval_loop(&heap, input), &Value::U64(16000));
+    assert_eq!(heap.closures_allocated(), 0);
+    assert_eq!(heap.u64s_allocated(), 3);
 }
 
 #[test]
@@ -310,7 +843,281 @@ fn test_eval_loop6() {
         &Expr::U64(7),
     ));
     let input = &Expr::App(apply_9_7, plus);
-    let output = &Value::U64(16);
-    let mut heap = Heap::with_capacity(1024);
-    assert_eq!(eval_loop(&mut heap, input), output)
+    assert_evaluates_to(input, 16)
+}
+
+#[test]
+fn test_eval_loop_saturated_add() {
+    let plus = &Expr::Lam(&Expr::Lam(&Expr::AddU64(&Expr::Var(0), &Expr::Var(1))));
+    // plus 9 7, which is recognized
+    let saturated = &Expr::App(&Expr::App(plus, &Expr::U64(9)), &Expr::U64(7));
+    // (\f -> f 9 7) plus, which isn't
+    let unsaturated = &Expr::App(
+        &Expr::Lam(&Expr::App(
+            &Expr::App(&Expr::Var(0), &Expr::U64(9)),
+            &Expr::U64(7),
+        )),
+        plus,
+    );
+
+    let saturated_heap = Heap::with_capacity(1024);
+    assert_eq!(eval_loop(&saturated_heap, saturated), &Value::U64(16));
+    let unsaturated_heap = Heap::with_capacity(1024);
+    assert_eq!(eval_loop(&unsaturated_heap, unsaturated), &Value::U64(16));
+
+    assert_eq!(saturated_heap.stats().closures, 0);
+    assert!(unsaturated_heap.stats().closures > saturated_heap.stats().closures)
+}
+
+#[test]
+fn test_eval_loop_cached() {
+    // (\a -> \b -> b) shared shared, where shared = (\x -> x) (\y -> y)
+    let shared = &Expr::App(&Expr::Lam(&Expr::Var(0)), &Expr::Lam(&Expr::Var(0)));
+    let input = &Expr::App(
+        &Expr::App(&Expr::Lam(&Expr::Lam(&Expr::Var(0))), shared),
+        shared,
+    );
+
+    let heap = Heap::with_capacity(1024);
+    let uncached = eval_loop(&heap, input);
+    let uncached_closures = heap.stats().closures;
+
+    let heap = Heap::with_capacity(1024);
+    let mut cache = EvalCache::new();
+    let cached = eval_loop_cached(&heap, &mut cache, input);
+    let cached_closures = heap.stats().closures;
+
+    assert_eq!(uncached, cached);
+    // evaluating `shared` builds two closures, and the second evaluation is skipped
+    assert_eq!(cached_closures, uncached_closures - 2)
+}
+
+#[cfg(test)]
+fn scott_numeral<'expr>(builder: &'expr ExprBuilder<'expr>, n: u64) -> ExprRef<'expr> {
+    // zero = \z -> \s -> z
+    // succ n = \z -> \s -> s n
+    let mut numeral = builder.mk_lam(builder.mk_lam(builder.mk_var(1)));
+    for _ in 0..n {
+        numeral = builder.mk_lam(builder.mk_lam(builder.mk_app(builder.mk_var(0), numeral)));
+    }
+    numeral
+}
+
+#[cfg(test)]
+fn factorial<'expr>(builder: &'expr ExprBuilder<'expr>) -> ExprRef<'expr> {
+    // mul = fix (\mul -> \m -> \k -> m 0 (\p -> k + mul p k))
+    let mul = builder.mk_fix(builder.mk_lam(builder.mk_lam(builder.mk_lam(builder.mk_app(
+        builder.mk_app(builder.mk_var(1), builder.mk_u64(0)),
+        builder.mk_lam(builder.mk_addu64(
+            builder.mk_var(1),
+            builder.mk_app(
+                builder.mk_app(builder.mk_var(3), builder.mk_var(0)),
+                builder.mk_var(1),
+            ),
+        )),
+    )))));
+    // fact = fix (\fact -> \m -> m 1 (\p -> mul m (fact p)))
+    builder.mk_fix(builder.mk_lam(builder.mk_lam(builder.mk_app(
+        builder.mk_app(builder.mk_var(0), builder.mk_u64(1)),
+        builder.mk_lam(builder.mk_app(
+            builder.mk_app(mul, builder.mk_var(1)),
+            builder.mk_app(builder.mk_var(2), builder.mk_var(0)),
+        )),
+    ))))
+}
+
+#[test]
+fn test_eval_fix1() {
+    let builder = ExprBuilder::new();
+    let input = builder.mk_app(factorial(&builder), scott_numeral(&builder, 3));
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(eval(&heap, &Vec::new(), input), &Value::U64(6))
+}
+
+#[test]
+fn test_eval_loop_fix1() {
+    let builder = ExprBuilder::new();
+    let input = builder.mk_app(factorial(&builder), scott_numeral(&builder, 4));
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(
+        eval_loop_with_fuel(&heap, input, 100000),
+        Option::Some(&Value::U64(24))
+    )
+}
+
+#[test]
+fn test_eval_loop_fuel1() {
+    // (\x -> x x) (\x -> x x)
+    let builder = ExprBuilder::new();
+    let omega = builder.mk_lam(builder.mk_app(builder.mk_var(0), builder.mk_var(0)));
+    let input = builder.mk_app(omega, omega);
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(eval_loop_with_fuel(&heap, input, 1000), Option::None)
+}
+
+#[test]
+fn test_eval_loop_shared_env1() {
+    // (\x -> (\f -> \g -> \s -> s f g) (\y -> x) (\z -> x)) 7
+    let builder = ExprBuilder::new();
+    let pair = builder.mk_lam(builder.mk_lam(builder.mk_lam(builder.mk_app(
+        builder.mk_app(builder.mk_var(0), builder.mk_var(2)),
+        builder.mk_var(1),
+    ))));
+    let input = builder.mk_app(
+        builder.mk_lam(builder.mk_app(
+            builder.mk_app(pair, builder.mk_lam(builder.mk_var(1))),
+            builder.mk_lam(builder.mk_var(1)),
+        )),
+        builder.mk_u64(7),
+    );
+    let heap = Heap::with_capacity(1024);
+    match eval_loop(&heap, input) {
+        Value::Closure { env, .. } => match (env.get(1), env.get(0)) {
+            (
+                Option::Some(Value::Closure { env: f_env, .. }),
+                Option::Some(Value::Closure { env: g_env, .. }),
+            ) => {
+                assert_eq!(f_env, &Env::from([&Value::U64(7)]));
+                assert!(Env::ptr_eq(f_env, g_env))
+            }
+            value => panic!("expected closures, got {:?}", value),
+        },
+        value => panic!("expected closure, got {:?}", value),
+    }
+}
+
+#[test]
+fn test_eval_loop_env_nodes() {
+    // (\x1 -> ... \x100 -> x1) 1 ... 100
+    let builder = ExprBuilder::new();
+    let mut body = builder.mk_var(99);
+    for _ in 0..100 {
+        body = builder.mk_lam(body);
+    }
+    let input = builder.mk_apps(body, (1..=100).map(|n| builder.mk_u64(n)).collect());
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(eval_loop(&heap, input), &Value::U64(1));
+    // Each application binds one variable by allocating one environment node. Copying the
+    // environment for each binding would have allocated 1 + 2 + ... + 100 = 5050 entries.
+    assert_eq!(heap.stats().env_nodes, 100)
+}
+
+#[cfg(test)]
+fn let_and_app<'expr>(builder: &'expr ExprBuilder<'expr>) -> (ExprRef<'expr>, ExprRef<'expr>) {
+    // let x = 9 in let f = \y -> x + y in f 7
+    let bound = builder.mk_u64(9);
+    let f = builder.mk_lam(builder.mk_addu64(builder.mk_var(1), builder.mk_var(0)));
+    let body = builder.mk_app(builder.mk_var(0), builder.mk_u64(7));
+    let with_let = builder.mk_let(bound, builder.mk_let(f, body));
+    let with_app = builder.mk_app(
+        builder.mk_lam(builder.mk_app(builder.mk_lam(body), f)),
+        bound,
+    );
+    (with_let, with_app)
+}
+
+#[test]
+fn test_eval_let1() {
+    let builder = ExprBuilder::new();
+    let (with_let, with_app) = let_and_app(&builder);
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(eval(&heap, &Vec::new(), with_let), &Value::U64(16));
+    assert_eq!(
+        eval(&heap, &Vec::new(), with_let),
+        eval(&heap, &Vec::new(), with_app)
+    )
+}
+
+#[test]
+fn test_eval_loop_let1() {
+    let builder = ExprBuilder::new();
+    let (with_let, with_app) = let_and_app(&builder);
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(eval_loop(&heap, with_let), &Value::U64(16));
+    assert_eq!(eval_loop(&heap, with_let), eval_loop(&heap, with_app))
+}
+
+#[test]
+fn test_reify1() {
+    // the result of test_eval3
+    let id = &Expr::Lam(&Expr::Var(0));
+    let konst = &Expr::Lam(&Expr::Lam(&Expr::Var(1)));
+    let input = &Expr::App(konst, id);
+    let heap = Heap::with_capacity(1024);
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        reify(&builder, eval(&heap, &Vec::new(), input)),
+        &Expr::Lam(&Expr::Lam(&Expr::Var(0)))
+    )
+}
+
+#[test]
+fn test_reify2() {
+    // the result of test_eval4
+    let id = &Expr::Lam(&Expr::Var(0));
+    let konst = &Expr::Lam(&Expr::Lam(&Expr::Var(1)));
+    let konst_id = &Expr::App(konst, id);
+    let input = &Expr::App(konst_id, konst);
+    let heap = Heap::with_capacity(1024);
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        reify(&builder, eval(&heap, &Vec::new(), input)),
+        &Expr::Lam(&Expr::Var(0))
+    )
+}
+
+#[test]
+fn test_reify3() {
+    // (\x -> \y -> \z -> x + z) 9 5
+    let input = &Expr::App(
+        &Expr::App(
+            &Expr::Lam(&Expr::Lam(&Expr::Lam(&Expr::AddU64(
+                &Expr::Var(2),
+                &Expr::Var(0),
+            )))),
+            &Expr::U64(9),
+        ),
+        &Expr::U64(5),
+    );
+    let heap = Heap::with_capacity(1024);
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        reify(&builder, eval_loop(&heap, input)),
+        &Expr::Lam(&Expr::AddU64(&Expr::U64(9), &Expr::Var(0)))
+    )
+}
+
+#[test]
+fn test_eval_loop_arbitrary() {
+    use arbitrary::{Arbitrary, Unstructured};
+    use ast::arbitrary::ArbitraryExpr;
+
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut bytes = vec![0; 256];
+    for _ in 0..500 {
+        for byte in bytes.iter_mut() {
+            // xorshift64
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            *byte = seed as u8;
+        }
+        let expr = match ArbitraryExpr::arbitrary(&mut Unstructured::new(&bytes)) {
+            Result::Ok(expr) => expr,
+            Result::Err(_) => continue,
+        };
+
+        // Ill-typed terms and overflowing additions are expected to fail, but every variable
+        // must be in scope.
+        let builder = ExprBuilder::new();
+        let heap = Heap::with_capacity(1024);
+        if let Result::Err(err) = try_eval_loop_with_fuel(&heap, expr.build(&builder), 1000) {
+            assert!(
+                !matches!(err, EvalError::UnboundIndex(_)),
+                "{:?} failed with {:?}",
+                expr,
+                err
+            );
+        }
+    }
 }
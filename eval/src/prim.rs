@@ -0,0 +1,18 @@
+//! The primitive operations on numbers. Overflow is an error rather than a panic or a wrap.
+
+use crate::EvalError;
+
+pub fn add(l: u64, r: u64) -> Result<u64, EvalError> {
+    l.checked_add(r).ok_or(EvalError::Overflow)
+}
+
+#[test]
+fn test_add1() {
+    assert_eq!(add(9, 7), Result::Ok(16))
+}
+
+#[test]
+fn test_add_overflow() {
+    assert_eq!(add(u64::MAX, 0), Result::Ok(u64::MAX));
+    assert_eq!(add(u64::MAX, 1), Result::Err(EvalError::Overflow))
+}
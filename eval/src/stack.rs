@@ -1,6 +1,6 @@
 use crate::value::Value;
 use num::Integer;
-use std::alloc::{GlobalAlloc, Layout, System};
+use std::alloc::{handle_alloc_error, GlobalAlloc, Layout, System};
 use std::fmt::Debug;
 use std::ops::Index;
 
@@ -13,10 +13,19 @@ pub struct Stack<'expr, 'value> {
 impl<'expr, 'value> Index<usize> for Stack<'expr, 'value> {
     type Output = &'value Value<'expr, 'value>;
     fn index<'stack>(&'stack self, ix: usize) -> &'stack Self::Output {
+        if ix >= self.size {
+            panic!("Stack::index failed: index {} out of bounds", ix)
+        }
         unsafe { &*self.buffer.offset(self.size as isize - ix as isize - 1) }
     }
 }
 
+impl<'expr, 'value> Drop for Stack<'expr, 'value> {
+    fn drop(&mut self) {
+        unsafe { System.dealloc(self.buffer.cast(), Self::layout(self.capacity)) }
+    }
+}
+
 impl<'expr, 'value> Debug for Stack<'expr, 'value> {
     fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         self.iter_from_bottom().collect::<Vec<_>>().fmt(formatter)
@@ -53,13 +62,23 @@ impl<'expr, 'value> Iterator for IterFromBottom<'expr, 'value> {
         if self.current == self.size {
             Option::None
         } else {
+            let val = unsafe { *self.base.offset(self.current as isize) };
             self.current += 1;
-            Option::Some(unsafe { *self.base.offset(self.current as isize) })
+            Option::Some(val)
         }
     }
 }
 
 impl<'expr, 'value> Stack<'expr, 'value> {
+    /// The layout of a buffer of `capacity` items. The buffer is never empty, because allocating
+    /// zero bytes is undefined behaviour.
+    fn layout(capacity: usize) -> Layout {
+        match Layout::array::<&Value>(std::cmp::max(capacity, 1)) {
+            Result::Ok(layout) => layout,
+            Result::Err(_) => panic!("Stack::with_capacity failed: capacity too large"),
+        }
+    }
+
     /// Create a stack with the given capacity in bytes. Panics if the capacity is exceeded.
     pub fn with_capacity(size_bytes: usize) -> Self {
         let (q, r) = size_bytes.div_rem(&std::mem::size_of::<&Value>());
@@ -67,17 +86,15 @@ impl<'expr, 'value> Stack<'expr, 'value> {
             true => 0,
             false => 1,
         };
+        let layout = Self::layout(size_items);
+        let buffer = unsafe { System.alloc(layout) };
+        if buffer.is_null() {
+            handle_alloc_error(layout)
+        }
         Stack {
             capacity: size_items,
             size: 0,
-            buffer: unsafe {
-                System
-                    .alloc(Layout::from_size_align_unchecked(
-                        size_items,
-                        std::mem::align_of::<&Value>(),
-                    ))
-                    .cast()
-            },
+            buffer: buffer.cast(),
         }
     }
 
@@ -90,11 +107,17 @@ impl<'expr, 'value> Stack<'expr, 'value> {
     }
 
     pub fn pop(&mut self) -> &'value Value<'expr, 'value> {
+        if self.size == 0 {
+            panic!("Stack::pop failed: stack underflow")
+        }
         self.size -= 1;
         unsafe { *self.buffer.offset(self.size as isize) }
     }
 
     pub fn peek(&self) -> &'value Value<'expr, 'value> {
+        if self.size == 0 {
+            panic!("Stack::peek failed: stack is empty")
+        }
         unsafe { *self.buffer.offset(self.size as isize - 1) }
     }
 
@@ -102,6 +125,12 @@ impl<'expr, 'value> Stack<'expr, 'value> {
         self.size
     }
 
+    /// The values on the stack, from the bottom up. Entry `i` of the stack is at
+    /// `size() - 1 - i` in the slice.
+    pub fn as_slice(&self) -> &[&'value Value<'expr, 'value>] {
+        unsafe { std::slice::from_raw_parts(self.buffer, self.size) }
+    }
+
     pub fn iter_from_top(&self) -> IterFromTop<'expr, 'value> {
         IterFromTop {
             remaining: self.size,
@@ -131,3 +160,40 @@ fn test_stack1() {
     assert_eq!(stack[1], &Value::U64(10));
     assert_eq!(stack[2], &Value::U64(999));
 }
+
+#[test]
+fn test_stack_iter() {
+    let mut stack = Stack::with_capacity(1024);
+    stack.push(&Value::U64(1));
+    stack.push(&Value::U64(2));
+    stack.push(&Value::U64(3));
+    assert_eq!(
+        stack.iter_from_bottom().collect::<Vec<_>>(),
+        vec![&Value::U64(1), &Value::U64(2), &Value::U64(3)]
+    );
+    assert_eq!(
+        stack.iter_from_top().collect::<Vec<_>>(),
+        vec![&Value::U64(3), &Value::U64(2), &Value::U64(1)]
+    );
+}
+
+#[test]
+fn test_stack_as_slice() {
+    let mut stack = Stack::with_capacity(1024);
+    stack.push(&Value::U64(1));
+    stack.push(&Value::U64(2));
+    stack.push(&Value::U64(3));
+    let slice = stack.as_slice();
+    assert_eq!(slice.len(), 3);
+    for ix in 0..3 {
+        assert_eq!(slice[stack.size() - 1 - ix], stack[ix]);
+    }
+    assert_eq!(slice, &[&Value::U64(1), &Value::U64(2), &Value::U64(3)]);
+}
+
+#[test]
+#[should_panic(expected = "Stack::pop failed: stack underflow")]
+fn test_stack_pop_empty() {
+    let mut stack = Stack::with_capacity(1024);
+    stack.pop();
+}
@@ -1,10 +1,98 @@
-use ast::de_bruijn::ExprRef;
+use crate::env::Env;
+use crate::reify;
+use ast::de_bruijn::{normalize, ExprBuilder, ExprRef};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Value<'expr, 'value> {
     U64(u64),
     Closure {
-        env: Vec<&'value Value<'expr, 'value>>,
+        env: Env<'expr, 'value>,
         body: ExprRef<'expr>,
     },
+    /// The value of a fixpoint `Fix(Lam(body))`. When it's applied, `body` is evaluated with the
+    /// fixpoint pushed onto `env`, and the resulting function is applied instead.
+    Fix {
+        env: Env<'expr, 'value>,
+        body: ExprRef<'expr>,
+    },
+}
+
+impl<'expr, 'value> Value<'expr, 'value> {
+    /// Compare values by what they compute rather than by how they're stored. Closures are
+    /// reified first, so two closures are equal when they capture the same values for the
+    /// variables they use, regardless of where those values sit in their environments.
+    ///
+    /// The reified terms are then normalized, so closures whose bodies only differ by redexes
+    /// are equal too. Normalization gives up after [`SEMANTIC_EQ_FUEL`] substitutions, and then
+    /// the reified terms are compared as they are.
+    ///
+    /// This is more expensive than `==`, which compares environments position by position.
+    pub fn semantic_eq(&self, other: &Value<'expr, 'value>) -> bool {
+        let builder = ExprBuilder::new();
+        let left = reify(&builder, self);
+        let right = reify(&builder, other);
+        match (
+            normalize(&builder, left, SEMANTIC_EQ_FUEL),
+            normalize(&builder, right, SEMANTIC_EQ_FUEL),
+        ) {
+            (Option::Some(left), Option::Some(right)) => left == right,
+            _ => left == right,
+        }
+    }
+}
+
+/// How many substitutions [`Value::semantic_eq`] makes while normalizing each side.
+pub const SEMANTIC_EQ_FUEL: usize = 1000;
+
+#[test]
+fn test_semantic_eq1() {
+    use ast::de_bruijn::Expr;
+
+    let a = &Value::U64(1);
+    let b = &Value::U64(2);
+    // \x -> b, where b is the most recently bound variable
+    let left = Value::Closure {
+        env: Env::from([a, b]),
+        body: &Expr::Var(1),
+    };
+    // \x -> b, where b is bound before a
+    let right = Value::Closure {
+        env: Env::from([b, a]),
+        body: &Expr::Var(2),
+    };
+    assert_ne!(left, right);
+    assert!(left.semantic_eq(&right));
+
+    let other = Value::Closure {
+        env: Env::from([b, a]),
+        body: &Expr::Var(1),
+    };
+    assert!(!left.semantic_eq(&other))
+}
+
+#[test]
+fn test_semantic_eq_normalize() {
+    use ast::de_bruijn::Expr;
+
+    // \x -> x
+    let left = Value::Closure {
+        env: Env::new(),
+        body: &Expr::Var(0),
+    };
+    // \x -> (\y -> y) x
+    let right = Value::Closure {
+        env: Env::new(),
+        body: &Expr::App(&Expr::Lam(&Expr::Var(0)), &Expr::Var(0)),
+    };
+    assert_ne!(left, right);
+    assert!(left.semantic_eq(&right));
+
+    // \x -> (\y -> y y) (\y -> y y) has no normal form
+    let omega = &Expr::Lam(&Expr::App(&Expr::Var(0), &Expr::Var(0)));
+    let diverges = Value::Closure {
+        env: Env::new(),
+        body: &Expr::App(omega, omega),
+    };
+    assert!(diverges.semantic_eq(&diverges.clone()));
+    assert!(!diverges.semantic_eq(&left));
 }
@@ -1,24 +1,60 @@
-use ast::Expr;
+use ast::syntax::{ExprBuilder, ExprRef};
 
 const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz";
+/// Words that the lexer reserves, which can't be used as identifiers.
+const KEYWORDS: &[&str] = &["let"];
+
+/// The relative likelihood of each kind of node that [`Generator::gen_expr`] picks while it has
+/// size left. Variables can only be picked when one is in scope. Once the size runs out, only
+/// leaves are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenWeights {
+    pub lam: u32,
+    pub app: u32,
+    pub ident: u32,
+}
+
+impl Default for GenWeights {
+    /// Lambdas and applications are equally likely, and variables only appear at the leaves.
+    fn default() -> Self {
+        GenWeights {
+            lam: 1,
+            app: 1,
+            ident: 0,
+        }
+    }
+}
+
+enum Node {
+    Lam,
+    App,
+    Ident,
+}
 
 pub struct Generator {
     idents: Vec<String>,
+    weights: GenWeights,
 }
 
 impl Generator {
     pub fn new() -> Self {
+        Generator::with_weights(GenWeights::default())
+    }
+
+    pub fn with_weights(weights: GenWeights) -> Self {
         let mut idents = Vec::new();
         let alphabet: Vec<char> = ALPHABET.chars().collect();
-        for _ in 0..100 {
+        while idents.len() < 100 {
             let length = (rand::random::<u8>() % 10) + 1;
             let mut ident = String::new();
             for _ in 0..length {
                 ident.push(alphabet[rand::random::<usize>() % 26])
             }
-            idents.push(ident);
+            if !KEYWORDS.contains(&ident.as_str()) {
+                idents.push(ident);
+            }
         }
-        Generator { idents }
+        Generator { idents, weights }
     }
 
     fn gen_ident<'gen>(&'gen self) -> &'gen str {
@@ -26,27 +62,167 @@ impl Generator {
         &self.idents[rand::random::<usize>() % existing_count]
     }
 
-    pub fn gen_expr<'gen>(&'gen self, size: u32) -> Expr<'gen> {
-        if size > 0 {
-            match rand::random::<u8>() % 2 {
-                0 => self.gen_lambda(size),
-                1 => self.gen_app(size),
-                _ => panic!("impossible"),
-            }
+    /// Generate a closed expression. Only lambdas, applications and variables are generated.
+    pub fn gen_expr<'gen, 'builder, 'expr>(
+        &'gen self,
+        builder: &'builder ExprBuilder<'gen, 'expr>,
+        size: u32,
+    ) -> ExprRef<'gen, 'expr>
+    where
+        'builder: 'expr,
+    {
+        self.gen_expr_in(builder, &mut Vec::new(), size)
+    }
+
+    /// Generate an expression whose free variables are all in `scope`.
+    fn gen_expr_in<'gen, 'builder, 'expr>(
+        &'gen self,
+        builder: &'builder ExprBuilder<'gen, 'expr>,
+        scope: &mut Vec<&'gen str>,
+        size: u32,
+    ) -> ExprRef<'gen, 'expr>
+    where
+        'builder: 'expr,
+    {
+        let node = if size > 0 {
+            self.choose_node(!scope.is_empty())
+        } else {
+            Node::Ident
+        };
+        match node {
+            Node::Lam => self.gen_lambda(builder, scope, size),
+            Node::App => self.gen_app(builder, scope, size),
+            Node::Ident => self.gen_leaf(builder, scope),
+        }
+    }
+
+    /// Pick a node according to the weights. If every weight that applies is 0, a leaf is
+    /// picked.
+    fn choose_node(&self, ident_in_scope: bool) -> Node {
+        let GenWeights { lam, app, ident } = self.weights;
+        let ident = if ident_in_scope { ident } else { 0 };
+        let total = lam + app + ident;
+        if total == 0 {
+            return Node::Ident;
+        }
+        let choice = rand::random::<u32>() % total;
+        if choice < lam {
+            Node::Lam
+        } else if choice < lam + app {
+            Node::App
+        } else {
+            Node::Ident
+        }
+    }
+
+    /// A variable from `scope`, or the identity function if there are none.
+    fn gen_leaf<'gen, 'builder, 'expr>(
+        &'gen self,
+        builder: &'builder ExprBuilder<'gen, 'expr>,
+        scope: &[&'gen str],
+    ) -> ExprRef<'gen, 'expr>
+    where
+        'builder: 'expr,
+    {
+        if scope.is_empty() {
+            // There's nothing to refer to, so the smallest closed term is the identity function.
+            let arg = self.gen_ident();
+            builder.mk_lam(arg, builder.mk_ident(arg))
+        } else {
+            builder.mk_ident(scope[rand::random::<usize>() % scope.len()])
+        }
+    }
+
+    /// Generate a closed expression that pretty prints to `tokens` tokens, not counting whitespace.
+    /// The smallest closed expression, `\x -> x`, has 4 tokens, so smaller budgets are rounded up.
+    pub fn gen_expr_with_token_budget<'gen, 'builder, 'expr>(
+        &'gen self,
+        builder: &'builder ExprBuilder<'gen, 'expr>,
+        tokens: usize,
+    ) -> ExprRef<'gen, 'expr>
+    where
+        'builder: 'expr,
+    {
+        self.gen_budgeted_in(builder, &mut Vec::new(), tokens, true)
+    }
+
+    /// Generate an expression of exactly `tokens` tokens whose free variables are all in `scope`.
+    ///
+    /// Applications are only generated when `scope` is non-empty, and their heads are never
+    /// lambdas (`allow_lambda` is false), so the only parentheses that get printed are around
+    /// compound arguments. Those are paid for before the argument is generated.
+    fn gen_budgeted_in<'gen, 'builder, 'expr>(
+        &'gen self,
+        builder: &'builder ExprBuilder<'gen, 'expr>,
+        scope: &mut Vec<&'gen str>,
+        tokens: usize,
+        allow_lambda: bool,
+    ) -> ExprRef<'gen, 'expr>
+    where
+        'builder: 'expr,
+    {
+        if scope.is_empty() {
+            // `\x ->` is 3 tokens, and the body needs at least 1.
+            let arg = self.gen_ident();
+            scope.push(arg);
+            let body = self.gen_budgeted_in(builder, scope, tokens.saturating_sub(3), true);
+            scope.pop();
+            return builder.mk_lam(arg, body);
+        }
+
+        if tokens <= 1 {
+            builder.mk_ident(scope[rand::random::<usize>() % scope.len()])
+        } else if tokens < 4 || !allow_lambda || rand::random::<bool>() {
+            // `l r`, or `l (r)` when `r` is more than one token.
+            let r_tokens = if tokens >= 5 && rand::random::<bool>() {
+                2 + rand::random::<usize>() % (tokens - 4)
+            } else {
+                1
+            };
+            let l_tokens = if r_tokens > 1 {
+                tokens - r_tokens - 2
+            } else {
+                tokens - 1
+            };
+            let l = self.gen_budgeted_in(builder, scope, l_tokens, false);
+            let r = self.gen_budgeted_in(builder, scope, r_tokens, true);
+            builder.mk_app(l, r)
         } else {
-            Expr::Ident(&self.gen_ident())
+            let arg = self.gen_ident();
+            scope.push(arg);
+            let body = self.gen_budgeted_in(builder, scope, tokens - 3, true);
+            scope.pop();
+            builder.mk_lam(arg, body)
         }
     }
 
-    fn gen_app<'gen>(&'gen self, size: u32) -> Expr<'gen> {
-        let l = self.gen_expr(size - 1);
-        let r = self.gen_expr(size - 1);
-        Expr::mk_app(l, r)
+    fn gen_app<'gen, 'builder, 'expr>(
+        &'gen self,
+        builder: &'builder ExprBuilder<'gen, 'expr>,
+        scope: &mut Vec<&'gen str>,
+        size: u32,
+    ) -> ExprRef<'gen, 'expr>
+    where
+        'builder: 'expr,
+    {
+        let l = self.gen_expr_in(builder, scope, size - 1);
+        let r = self.gen_expr_in(builder, scope, size - 1);
+        builder.mk_app(l, r)
     }
 
-    fn gen_lambda<'gen>(&'gen self, size: u32) -> Expr<'gen> {
-        let arg = &self.gen_ident();
-        let body = self.gen_expr(size - 1);
-        Expr::mk_lam(arg, body)
+    fn gen_lambda<'gen, 'builder, 'expr>(
+        &'gen self,
+        builder: &'builder ExprBuilder<'gen, 'expr>,
+        scope: &mut Vec<&'gen str>,
+        size: u32,
+    ) -> ExprRef<'gen, 'expr>
+    where
+        'builder: 'expr,
+    {
+        let arg = self.gen_ident();
+        scope.push(arg);
+        let body = self.gen_expr_in(builder, scope, size - 1);
+        scope.pop();
+        builder.mk_lam(arg, body)
     }
 }
@@ -1,7 +1,8 @@
 mod lib;
 
+use ast::syntax::ExprBuilder;
 use lib::Generator;
-use pretty::pretty_expr;
+use pretty::pretty_syntax;
 use std::fs::File;
 use std::io::Write;
 use std::str::FromStr;
@@ -9,9 +10,20 @@ use std::str::FromStr;
 fn run() -> std::io::Result<()> {
     let args: Vec<String> = std::env::args().collect();
     let gen = Generator::new();
-    let expr = gen.gen_expr(u32::from_str(&args[1]).unwrap());
-    let mut file = File::create(&args[2])?;
-    write!(file, "{}", pretty_expr(expr))
+    let builder = ExprBuilder::new();
+    // `generate SIZE FILE` or `generate --tokens TOKENS FILE`
+    let (expr, path) = match args[1].as_str() {
+        "--tokens" => (
+            gen.gen_expr_with_token_budget(&builder, usize::from_str(&args[2]).unwrap()),
+            &args[3],
+        ),
+        size => (
+            gen.gen_expr(&builder, u32::from_str(size).unwrap()),
+            &args[2],
+        ),
+    };
+    let mut file = File::create(path)?;
+    write!(file, "{}", pretty_syntax(expr))
 }
 
 fn main() {
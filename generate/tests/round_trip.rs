@@ -0,0 +1,31 @@
+use ast::syntax::{alpha_eq, ExprBuilder};
+use generate::Generator;
+use parser::parse_str;
+use pretty::pretty_syntax;
+
+/// Pretty printing a closed expression and parsing the result should give back the same
+/// expression, up to alpha equivalence and parentheses.
+///
+/// `let` isn't covered: the generator doesn't produce it, because the parser doesn't accept it
+/// yet.
+#[test]
+fn test_pretty_parse_round_trip() {
+    let gen = Generator::new();
+    for size in 0..8 {
+        for _ in 0..50 {
+            let builder = ExprBuilder::new();
+            let expr = gen.gen_expr(&builder, size);
+            let printed = pretty_syntax(expr);
+
+            let parse_builder = ExprBuilder::new();
+            let parsed = parse_str(&parse_builder, &printed)
+                .unwrap_or_else(|err| panic!("failed to parse {:?}: {:?}", printed, err));
+            assert!(
+                alpha_eq(expr, parsed),
+                "{:?} parsed as {:?}",
+                printed,
+                parsed
+            );
+        }
+    }
+}
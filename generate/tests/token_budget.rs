@@ -0,0 +1,41 @@
+use ast::syntax::ExprBuilder;
+use generate::Generator;
+use lexer::{significant, Lexer, TokenType};
+use pretty::pretty_syntax;
+use span::Offset;
+
+fn count_tokens(printed: &str) -> usize {
+    let tokens = Lexer::from_str(printed, Offset(0))
+        .tokenize()
+        .unwrap_or_else(|err| panic!("failed to lex {:?}: {:?}", printed, err));
+    significant(&tokens)
+        .filter(|token| token.token_type() != TokenType::Eof)
+        .count()
+}
+
+/// The pretty printed output of `gen_expr_with_token_budget` should have as many tokens as were
+/// asked for.
+#[test]
+fn test_gen_expr_with_token_budget() {
+    let gen = Generator::new();
+    for &budget in &[4, 5, 10, 100, 1000] {
+        for _ in 0..20 {
+            let builder = ExprBuilder::new();
+            let expr = gen.gen_expr_with_token_budget(&builder, budget);
+            let printed = pretty_syntax(expr);
+            assert_eq!(count_tokens(&printed), budget, "{:?}", printed);
+        }
+    }
+}
+
+/// Budgets that are too small for a closed expression give the identity function.
+#[test]
+fn test_gen_expr_with_token_budget_small() {
+    let gen = Generator::new();
+    for budget in 0..4 {
+        let builder = ExprBuilder::new();
+        let expr = gen.gen_expr_with_token_budget(&builder, budget);
+        let printed = pretty_syntax(expr);
+        assert_eq!(count_tokens(&printed), 4, "{:?}", printed);
+    }
+}
@@ -0,0 +1,55 @@
+use ast::syntax::{Expr, ExprBuilder, ExprRef};
+use generate::{GenWeights, Generator};
+
+/// `(lambdas, applications)`
+fn count_lams_and_apps(expr: ExprRef) -> (usize, usize) {
+    match expr {
+        Expr::Lam(_, body) => {
+            let (lams, apps) = count_lams_and_apps(body);
+            (lams + 1, apps)
+        }
+        Expr::App(l, r) => {
+            let (l_lams, l_apps) = count_lams_and_apps(l);
+            let (r_lams, r_apps) = count_lams_and_apps(r);
+            (l_lams + r_lams, l_apps + r_apps + 1)
+        }
+        Expr::Parens(inner) | Expr::Ann(inner, _) => count_lams_and_apps(inner),
+        Expr::Let(_, bound, body) => {
+            let (b_lams, b_apps) = count_lams_and_apps(bound);
+            let (lams, apps) = count_lams_and_apps(body);
+            (b_lams + lams, b_apps + apps)
+        }
+        Expr::Ident(_) => (0, 0),
+    }
+}
+
+fn sample(weights: GenWeights) -> (usize, usize) {
+    let gen = Generator::with_weights(weights);
+    let mut total = (0, 0);
+    for _ in 0..200 {
+        let builder = ExprBuilder::new();
+        let (lams, apps) = count_lams_and_apps(gen.gen_expr(&builder, 6));
+        total = (total.0 + lams, total.1 + apps);
+    }
+    total
+}
+
+#[test]
+fn test_gen_weights_lam_heavy() {
+    let (lams, apps) = sample(GenWeights {
+        lam: 9,
+        app: 1,
+        ident: 0,
+    });
+    assert!(lams > apps, "{} lambdas, {} applications", lams, apps)
+}
+
+#[test]
+fn test_gen_weights_app_heavy() {
+    let (lams, apps) = sample(GenWeights {
+        lam: 1,
+        app: 9,
+        ident: 1,
+    });
+    assert!(apps > lams, "{} lambdas, {} applications", lams, apps)
+}
@@ -1,19 +1,28 @@
 use errors::Highlight;
-use span::{Offset, SourceFile, Span};
+use span::{Offset, SourceFile, SourceFiles, Span};
 use std::convert::TryInto;
 use std::fmt::Display;
+use std::path::Path;
 use std::str::Chars;
 
-#[derive(Debug, PartialEq, Eq)]
+/// The derived ordering follows declaration order, which must match `to_usize`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TokenType {
     Space,
     Newline,
+    Comment,
     Backslash,
     Ident,
     RArrow,
     LParen,
     RParen,
     Equals,
+    Minus,
+    Colon,
+    Comma,
+    Let,
+    Indent,
+    Dedent,
     Eof,
 }
 
@@ -22,29 +31,93 @@ impl Display for TokenType {
         formatter.write_str(match self {
             TokenType::Space => "' '",
             TokenType::Newline => "newline",
+            TokenType::Comment => "comment",
             TokenType::Backslash => "'\\'",
             TokenType::Ident => "identifier",
             TokenType::RArrow => "'->'",
             TokenType::LParen => "'('",
             TokenType::RParen => "')'",
             TokenType::Equals => "'='",
+            TokenType::Minus => "'-'",
+            TokenType::Colon => "':'",
+            TokenType::Comma => "','",
+            TokenType::Let => "'let'",
+            TokenType::Indent => "indent",
+            TokenType::Dedent => "dedent",
             TokenType::Eof => "end of input",
         })
     }
 }
 
 impl TokenType {
+    /// Every token type, in `to_usize` order.
+    pub fn all() -> Vec<TokenType> {
+        vec![
+            TokenType::Space,
+            TokenType::Newline,
+            TokenType::Comment,
+            TokenType::Backslash,
+            TokenType::Ident,
+            TokenType::RArrow,
+            TokenType::LParen,
+            TokenType::RParen,
+            TokenType::Equals,
+            TokenType::Minus,
+            TokenType::Colon,
+            TokenType::Comma,
+            TokenType::Let,
+            TokenType::Indent,
+            TokenType::Dedent,
+            TokenType::Eof,
+        ]
+    }
+
     pub fn to_usize(&self) -> usize {
         match self {
             TokenType::Space => 0,
             TokenType::Newline => 1,
-            TokenType::Backslash => 2,
-            TokenType::Ident => 3,
-            TokenType::RArrow => 4,
-            TokenType::LParen => 5,
-            TokenType::RParen => 6,
-            TokenType::Equals => 7,
-            TokenType::Eof => 8,
+            TokenType::Comment => 2,
+            TokenType::Backslash => 3,
+            TokenType::Ident => 4,
+            TokenType::RArrow => 5,
+            TokenType::LParen => 6,
+            TokenType::RParen => 7,
+            TokenType::Equals => 8,
+            TokenType::Minus => 9,
+            TokenType::Colon => 10,
+            TokenType::Comma => 11,
+            TokenType::Let => 12,
+            TokenType::Indent => 13,
+            TokenType::Dedent => 14,
+            TokenType::Eof => 15,
+        }
+    }
+
+    /// The broad class of the token type, for things like syntax highlighting that don't care
+    /// about individual tokens.
+    pub fn category(&self) -> Category {
+        match self {
+            TokenType::Space | TokenType::Newline | TokenType::Comment => Category::Trivia,
+            TokenType::Backslash
+            | TokenType::RArrow
+            | TokenType::LParen
+            | TokenType::RParen
+            | TokenType::Equals
+            | TokenType::Minus
+            | TokenType::Colon
+            | TokenType::Comma => Category::Punctuation,
+            TokenType::Ident => Category::Name,
+            TokenType::Let => Category::Keyword,
+            TokenType::Indent | TokenType::Dedent => Category::Layout,
+            TokenType::Eof => Category::Eof,
+        }
+    }
+
+    /// The word a keyword is spelled with, or `None` if the token type isn't a keyword.
+    pub fn keyword(&self) -> Option<&'static str> {
+        match self {
+            TokenType::Let => Option::Some("let"),
+            _ => Option::None,
         }
     }
 
@@ -52,91 +125,258 @@ impl TokenType {
         match i {
             0 => TokenType::Space,
             1 => TokenType::Newline,
-            2 => TokenType::Backslash,
-            3 => TokenType::Ident,
-            4 => TokenType::RArrow,
-            5 => TokenType::LParen,
-            6 => TokenType::RParen,
-            7 => TokenType::Equals,
-            8 => TokenType::Eof,
+            2 => TokenType::Comment,
+            3 => TokenType::Backslash,
+            4 => TokenType::Ident,
+            5 => TokenType::RArrow,
+            6 => TokenType::LParen,
+            7 => TokenType::RParen,
+            8 => TokenType::Equals,
+            9 => TokenType::Minus,
+            10 => TokenType::Colon,
+            11 => TokenType::Comma,
+            12 => TokenType::Let,
+            13 => TokenType::Indent,
+            14 => TokenType::Dedent,
+            15 => TokenType::Eof,
             _ => panic!("unsafe_from_usize failed"),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// See [`TokenType::category`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    /// Spaces, newlines and comments
+    Trivia,
+    /// Symbols, such as `\`, `->` and `(`
+    Punctuation,
+    /// Identifiers
+    Name,
+    /// Reserved words, such as `let`
+    Keyword,
+    /// `Indent` and `Dedent`, which are synthesized by the lexer rather than written
+    Layout,
+    Eof,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TokenData<'src> {
     Space,
     Newline,
+    /// A line comment. Contains the text following the `--`, up to (but not including) the
+    /// newline.
+    Comment(&'src str),
     Backslash,
     Ident(&'src str),
     RArrow,
     LParen,
     RParen,
     Equals,
+    Minus,
+    Colon,
+    Comma,
+    Let,
+    /// The start of a line that is indented further than the previous one. Only produced in
+    /// layout mode.
+    Indent,
+    /// The start of a line that is indented less than the previous one. One `Dedent` is produced
+    /// for each enclosing `Indent` that the line closes. Only produced in layout mode.
+    Dedent,
     Eof,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Token<'src> {
     pub data: TokenData<'src>,
     pub span: Span,
 }
 
 impl<'src> Token<'src> {
+    /// A token covering `length` bytes from offset `start`.
+    pub fn new(data: TokenData<'src>, start: u32, length: u32) -> Self {
+        Token {
+            data,
+            span: Span {
+                start: Offset(start),
+                length: Offset(length),
+            },
+        }
+    }
+
     #[inline]
     pub fn token_type(&self) -> TokenType {
         match self.data {
             TokenData::Space => TokenType::Space,
             TokenData::Newline => TokenType::Newline,
+            TokenData::Comment(_) => TokenType::Comment,
             TokenData::Backslash => TokenType::Backslash,
             TokenData::Ident(_) => TokenType::Ident,
             TokenData::RArrow => TokenType::RArrow,
             TokenData::LParen => TokenType::LParen,
             TokenData::RParen => TokenType::RParen,
             TokenData::Equals => TokenType::Equals,
+            TokenData::Minus => TokenType::Minus,
+            TokenData::Colon => TokenType::Colon,
+            TokenData::Comma => TokenType::Comma,
+            TokenData::Let => TokenType::Let,
+            TokenData::Indent => TokenType::Indent,
+            TokenData::Dedent => TokenType::Dedent,
             TokenData::Eof => TokenType::Eof,
         }
     }
+
+    /// Spaces, newlines and comments, which don't affect the meaning of a program.
+    #[inline]
+    pub fn is_trivia(&self) -> bool {
+        self.token_type().category() == Category::Trivia
+    }
+
+    #[inline]
+    pub fn is_significant(&self) -> bool {
+        !self.is_trivia()
+    }
+
+    /// The source text of the token. `src` must be the file the token was lexed from. The `Eof`
+    /// token, whose span points one past the end of the input, has no text.
+    pub fn text<'a>(&self, src: &'a SourceFile) -> &'a str {
+        match self.data {
+            TokenData::Eof => "",
+            _ => src.slice(self.span),
+        }
+    }
+}
+
+/// The tokens that aren't trivia. For input that has already been lexed, this is the token stream
+/// [`Lexer::with_skip_trivia`] would have produced.
+pub fn significant<'a, 'src>(tokens: &'a [Token<'src>]) -> impl Iterator<Item = &'a Token<'src>> {
+    tokens.iter().filter(|token| token.is_significant())
 }
 
 pub struct Lexer<'src> {
-    src_file: &'src SourceFile,
+    content: &'src str,
+    /// offset of the beginning of `content`
+    start: Offset,
     current: Option<char>,
     position: Chars<'src>,
     /// offset in bytes; *not* characters (we assume UTF-8 encoding)
     offset: Offset,
+    /// maximum identifier length in characters; `None` means unlimited
+    max_ident_len: Option<usize>,
+    /// emit `Indent` and `Dedent` tokens
+    layout: bool,
+    /// the current line number, starting from 1
+    line: usize,
+    /// offset of the first character of the current line
+    line_start: Offset,
+    /// wrap errors in [`Error::Positioned`]
+    positions: bool,
+    /// don't emit trivia tokens
+    skip_trivia: bool,
+}
+
+fn is_newline(c: char) -> bool {
+    c == '\n'
 }
 
 fn is_ident_start(c: char) -> bool {
-    ('a' <= c && c <= 'z') || (c == '_')
+    c.is_alphabetic() || (c == '_')
 }
 
 fn is_ident_body(c: char) -> bool {
-    ('a' <= c && c <= 'z') || ('A' <= c && c <= 'Z') || ('0' <= c && c <= '9') || (c == '_')
+    c.is_alphanumeric() || (c == '_')
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Error {
     Unexpected(char, Offset),
     UnexpectedEof(Offset),
+    /// The identifier went over the configured maximum length. The `Offset` points at the first
+    /// character past the limit.
+    IdentTooLong(usize, Offset),
+    /// In layout mode, a line was indented less than the previous one, but its indentation
+    /// doesn't match any of the enclosing lines.
+    BadDedent(Offset),
+    /// A recovering tokenizer found more errors than it was allowed to report, and stopped here.
+    TooManyErrors(Offset),
+    /// An error annotated with its 1-based line and column, so that it can be described without
+    /// the [`SourceFiles`] it came from. Only produced by lexers built with
+    /// [`Lexer::with_positions`].
+    Positioned {
+        line: usize,
+        column: usize,
+        error: Box<Error>,
+    },
 }
 
 impl Error {
+    pub fn offset(&self) -> Offset {
+        match self {
+            Error::Unexpected(_, offset) => *offset,
+            Error::UnexpectedEof(offset) => *offset,
+            Error::IdentTooLong(_, offset) => *offset,
+            Error::BadDedent(offset) => *offset,
+            Error::TooManyErrors(offset) => *offset,
+            Error::Positioned { error, .. } => error.offset(),
+        }
+    }
+
+    /// The error's message, prefixed with its line and column when they're known.
+    pub fn message_with_position(&self) -> String {
+        match self {
+            Error::Positioned {
+                line,
+                column,
+                error,
+            } => format!(
+                "line {}, col {}: {}",
+                line,
+                column,
+                error.reportable().message
+            ),
+            _ => self.reportable().message,
+        }
+    }
+
     pub fn reportable(&self) -> errors::Error {
         match self {
-            Error::Unexpected(c, offset) => errors::Error {
-                highlight: Highlight::Point(*offset),
-                message: format!("Unexpected symbol '{}'", c),
-            },
-            Error::UnexpectedEof(offset) => errors::Error {
-                highlight: Highlight::Point(*offset),
-                message: String::from("Unexpected end of input"),
-            },
+            Error::Unexpected(c, offset) => errors::Error::at(Highlight::Point(*offset))
+                .message(format!("Unexpected symbol '{}'", c)),
+            Error::UnexpectedEof(offset) => {
+                errors::Error::at(Highlight::Point(*offset)).message("Unexpected end of input")
+            }
+            Error::IdentTooLong(max, offset) => errors::Error::at(Highlight::Point(*offset))
+                .message(format!("Identifier is longer than {} characters", max)),
+            Error::BadDedent(offset) => errors::Error::at(Highlight::Point(*offset))
+                .message("Indentation doesn't match any enclosing block"),
+            Error::TooManyErrors(offset) => {
+                errors::Error::at(Highlight::Point(*offset)).message("Too many errors; giving up")
+            }
+            Error::Positioned { error, .. } => error.reportable(),
         }
     }
 }
 
+impl From<Error> for errors::Error {
+    fn from(err: Error) -> Self {
+        err.reportable()
+    }
+}
+
+impl From<Error> for errors::Diagnostic {
+    fn from(err: Error) -> Self {
+        err.reportable().into()
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        formatter.write_str(&self.message_with_position())
+    }
+}
+
+impl std::error::Error for Error {}
+
 pub type LexerResult<T> = Result<T, Error>;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -148,13 +388,68 @@ pub enum NextToken<'src> {
 
 impl<'src> Lexer<'src> {
     pub fn from_source_file(src_file: &'src SourceFile) -> Self {
-        let mut position = src_file.data().chars();
+        Lexer::from_str(src_file.data(), src_file.get_start())
+    }
+
+    /// Lex `content` as if it began at `start`, without needing a [`SourceFile`].
+    pub fn from_str(content: &'src str, start: Offset) -> Self {
+        let mut position = content.chars();
         let current = position.next();
         Lexer {
-            src_file,
+            content,
+            start,
             current,
             position,
-            offset: src_file.get_start(),
+            offset: start,
+            max_ident_len: Option::None,
+            layout: false,
+            line: 1,
+            line_start: start,
+            positions: false,
+            skip_trivia: false,
+        }
+    }
+
+    /// Reject identifiers longer than `max` characters.
+    pub fn with_max_ident_len(mut self, max: usize) -> Self {
+        self.max_ident_len = Option::Some(max);
+        self
+    }
+
+    /// Emit `Indent` and `Dedent` tokens when the indentation changes between lines. Lines that
+    /// contain only spaces and comments don't affect the indentation.
+    pub fn with_layout(mut self) -> Self {
+        self.layout = true;
+        self
+    }
+
+    /// Report errors as [`Error::Positioned`], with the line and column at which they occurred.
+    pub fn with_positions(mut self) -> Self {
+        self.positions = true;
+        self
+    }
+
+    /// Leave spaces, newlines and comments out of the tokens, for clients that only care about
+    /// significant tokens. Layout tokens are still emitted if layout is enabled, and the token
+    /// stream is no longer lossless.
+    pub fn with_skip_trivia(mut self) -> Self {
+        self.skip_trivia = true;
+        self
+    }
+
+    /// Attach a line and column to `err`, if this lexer reports positions. The error must have
+    /// occurred on the current line.
+    fn positioned(&self, err: Error) -> Error {
+        if self.positions {
+            let start = self.line_start.subtract(self.start.to_u32()).to_usize();
+            let end = err.offset().subtract(self.start.to_u32()).to_usize();
+            Error::Positioned {
+                line: self.line,
+                column: self.content[start..end].chars().count() + 1,
+                error: Box::new(err),
+            }
+        } else {
+            err
         }
     }
 
@@ -163,27 +458,59 @@ impl<'src> Lexer<'src> {
         self.current
     }
 
+    /// Get the source text covered by `span`. The lexer only produces spans that lie on
+    /// character boundaries.
+    fn slice(&self, span: Span) -> &'src str {
+        let start = span.start.subtract(self.start.to_u32()).to_usize();
+        let end = span.end().subtract(self.start.to_u32()).to_usize();
+        &self.content[start..end]
+    }
+
     fn consume(&mut self) {
         if let Some(c) = self.current {
             self.offset.add_mut(c.len_utf8().try_into().unwrap());
+            if is_newline(c) {
+                self.line += 1;
+                self.line_start = self.offset;
+            }
         }
         self.current = self.position.next();
     }
 
-    fn consume_ident_body(&mut self, start_offset: Offset) -> Token<'src> {
+    /// Assumes that the first character of the identifier has already been consumed.
+    fn consume_ident_body(&mut self, start_offset: Offset) -> LexerResult<Token<'src>> {
+        let mut len = 1;
         while let Some(c) = self.lookahead() {
             if !is_ident_body(c) {
                 break;
             }
+            if let Option::Some(max) = self.max_ident_len {
+                if len >= max {
+                    return Result::Err(Error::IdentTooLong(max, self.offset));
+                }
+            }
             self.consume();
+            len += 1;
         }
-        let end_offset = self.offset;
-        let data =
-            TokenData::Ident(&self.src_file.data()[start_offset.to_usize()..end_offset.to_usize()]);
-        let span = Span {
-            start: start_offset,
-            length: end_offset.subtract(start_offset.to_u32()),
+        let span = self.span_from(start_offset);
+        let data = match self.slice(span) {
+            "let" => TokenData::Let,
+            ident => TokenData::Ident(ident),
         };
+        Result::Ok(Token { data, span })
+    }
+
+    /// Assumes that the leading `--` has already been consumed.
+    fn consume_comment(&mut self, start_offset: Offset) -> Token<'src> {
+        let text_offset = self.offset;
+        while let Some(c) = self.lookahead() {
+            if is_newline(c) {
+                break;
+            }
+            self.consume();
+        }
+        let data = TokenData::Comment(self.slice(self.span_from(text_offset)));
+        let span = self.span_from(start_offset);
         Token { data, span }
     }
 
@@ -191,17 +518,14 @@ impl<'src> Lexer<'src> {
         Error::Unexpected(c, self.offset)
     }
 
-    fn unexpected_eof(&self) -> Error {
-        Error::UnexpectedEof(self.offset)
+    /// The span from `start_offset` up to (but not including) the current character.
+    fn span_from(&self, start_offset: Offset) -> Span {
+        Span::new(start_offset, self.offset.subtract(start_offset.to_u32()))
     }
 
     fn emit(&mut self, start_offset: Offset, data: TokenData<'src>) -> NextToken<'src> {
         self.consume();
-        let end_offset = self.offset;
-        let span = Span {
-            start: start_offset,
-            length: end_offset.subtract(start_offset.to_u32()),
-        };
+        let span = self.span_from(start_offset);
         NextToken::Token(Token { data, span })
     }
 
@@ -214,54 +538,318 @@ impl<'src> Lexer<'src> {
                 ' ' => self.emit(start_offset, TokenData::Space),
                 '\\' => self.emit(start_offset, TokenData::Backslash),
                 '-' =>
-                // RArrow
+                // RArrow, Comment or Minus
                 {
                     self.consume();
+                    let minus = self.span_from(start_offset);
                     match self.lookahead() {
-                        Option::Some('>') => self.emit(start_offset, TokenData::RArrow),
-                        Option::Some(c) => NextToken::Error(self.unexpected(c)),
-                        Option::None => NextToken::Error(self.unexpected_eof()),
+                        Option::Some('>') => {
+                            let gt_offset = self.offset;
+                            self.consume();
+                            let gt = self.span_from(gt_offset);
+                            NextToken::Token(Token {
+                                data: TokenData::RArrow,
+                                span: minus.merge(gt),
+                            })
+                        }
+                        Option::Some('-') => {
+                            self.consume();
+                            NextToken::Token(self.consume_comment(start_offset))
+                        }
+                        _ => NextToken::Token(Token {
+                            data: TokenData::Minus,
+                            span: minus,
+                        }),
                     }
                 }
                 '(' => self.emit(start_offset, TokenData::LParen),
                 ')' => self.emit(start_offset, TokenData::RParen),
-                '=' => self.emit(start_offset, TokenData::Equals),
+                '=' =>
+                // RArrow (written `=>`) or Equals
+                {
+                    self.consume();
+                    let equals = self.span_from(start_offset);
+                    match self.lookahead() {
+                        Option::Some('>') => {
+                            let gt_offset = self.offset;
+                            self.consume();
+                            let gt = self.span_from(gt_offset);
+                            NextToken::Token(Token {
+                                data: TokenData::RArrow,
+                                span: equals.merge(gt),
+                            })
+                        }
+                        _ => NextToken::Token(Token {
+                            data: TokenData::Equals,
+                            span: equals,
+                        }),
+                    }
+                }
+                ':' => self.emit(start_offset, TokenData::Colon),
+                ',' => self.emit(start_offset, TokenData::Comma),
                 _ if is_ident_start(c) => {
                     self.consume();
-                    NextToken::Token(self.consume_ident_body(start_offset))
+                    match self.consume_ident_body(start_offset) {
+                        Result::Ok(token) => NextToken::Token(token),
+                        Result::Err(err) => NextToken::Error(err),
+                    }
                 }
                 _ => NextToken::Error(self.unexpected(c)),
             },
         }
     }
 
-    pub fn tokenize(mut self) -> LexerResult<Vec<Token<'src>>> {
+    pub fn tokenize(self) -> LexerResult<Vec<Token<'src>>> {
+        let (tokens, mut errors) = self.__tokenize(1);
+        match errors.pop() {
+            Option::None => Result::Ok(tokens),
+            Option::Some(err) => Result::Err(err),
+        }
+    }
+
+    /// Tokenize the whole input, skipping over errors instead of stopping at the first one.
+    ///
+    /// At most `max_errors` errors are reported. If there are more, lexing stops at the next one,
+    /// which is reported as [`Error::TooManyErrors`]. The tokens are always terminated by an `Eof`.
+    pub fn tokenize_recovering(self, max_errors: usize) -> (Vec<Token<'src>>, Vec<Error>) {
+        let (tokens, mut errors) = self.__tokenize(max_errors.saturating_add(1));
+        if errors.len() > max_errors {
+            if let Option::Some(err) = errors.pop() {
+                let too_many = match err {
+                    Error::Positioned {
+                        line,
+                        column,
+                        error,
+                    } => Error::Positioned {
+                        line,
+                        column,
+                        error: Box::new(Error::TooManyErrors(error.offset())),
+                    },
+                    err => Error::TooManyErrors(err.offset()),
+                };
+                errors.push(too_many);
+            }
+        }
+        (tokens, errors)
+    }
+
+    /// Skip the input that caused `err`.
+    fn recover(&mut self, err: &Error) {
+        match err {
+            Error::Unexpected(_, _) => self.consume(),
+            Error::IdentTooLong(_, _) => {
+                while let Some(c) = self.lookahead() {
+                    if !is_ident_body(c) {
+                        break;
+                    }
+                    self.consume();
+                }
+            }
+            Error::UnexpectedEof(_) | Error::BadDedent(_) | Error::TooManyErrors(_) => {}
+            Error::Positioned { error, .. } => self.recover(error),
+        }
+    }
+
+    /// Tokenize until the end of input, or until `stop_after` errors have been found.
+    fn __tokenize(mut self, stop_after: usize) -> (Vec<Token<'src>>, Vec<Error>) {
         let mut tokens = Vec::with_capacity(2048);
+        let mut errors = Vec::new();
+        // Layout state: the enclosing indentation levels, whether we're in the leading spaces of a
+        // line, and how many of those spaces we've seen.
+        let mut indents: Vec<usize> = vec![0];
+        let mut line_start = true;
+        let mut indent = 0;
         loop {
             match self.next_token() {
                 NextToken::Done => {
-                    let offset = self.offset;
-                    tokens.push(Token {
-                        data: TokenData::Eof,
-                        span: Span {
-                            start: offset,
-                            length: Offset(1),
-                        },
-                    });
                     break;
                 }
                 NextToken::Token(token) => {
-                    tokens.push(token);
+                    if self.layout {
+                        match token.data {
+                            TokenData::Newline => {
+                                line_start = true;
+                                indent = 0;
+                            }
+                            TokenData::Space if line_start => {
+                                indent += 1;
+                            }
+                            TokenData::Comment(_) => {}
+                            _ if line_start => {
+                                line_start = false;
+                                if let Result::Err(err) =
+                                    layout(&mut indents, indent, token.span.start, &mut tokens)
+                                {
+                                    errors.push(self.positioned(err));
+                                    if errors.len() >= stop_after {
+                                        break;
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    if !(self.skip_trivia && token.is_trivia()) {
+                        tokens.push(token);
+                    }
                 }
                 NextToken::Error(err) => {
-                    return Result::Err(err);
+                    let err = self.positioned(err);
+                    self.recover(&err);
+                    errors.push(err);
+                    if errors.len() >= stop_after {
+                        break;
+                    }
                 }
             }
         }
-        Result::Ok(tokens)
+
+        let offset = self.offset;
+        if self.layout {
+            while indents.len() > 1 {
+                indents.pop();
+                tokens.push(layout_token(TokenData::Dedent, offset));
+            }
+        }
+        // There's no character to cover, so the span is empty.
+        tokens.push(Token {
+            data: TokenData::Eof,
+            span: Span {
+                start: offset,
+                length: Offset(0),
+            },
+        });
+        (tokens, errors)
+    }
+}
+
+fn layout_token<'src>(data: TokenData<'src>, offset: Offset) -> Token<'src> {
+    Token {
+        data,
+        span: Span {
+            start: offset,
+            length: Offset(0),
+        },
     }
 }
 
+/// Emit the layout tokens for a line whose first significant token is at `offset`, and which is
+/// indented by `indent` spaces.
+fn layout(
+    indents: &mut Vec<usize>,
+    indent: usize,
+    offset: Offset,
+    tokens: &mut Vec<Token>,
+) -> LexerResult<()> {
+    let current = *indents.last().unwrap();
+    if indent > current {
+        indents.push(indent);
+        tokens.push(layout_token(TokenData::Indent, offset));
+    } else {
+        while indent < *indents.last().unwrap() {
+            indents.pop();
+            tokens.push(layout_token(TokenData::Dedent, offset));
+        }
+        if indent != *indents.last().unwrap() {
+            return Result::Err(Error::BadDedent(offset));
+        }
+    }
+    Result::Ok(())
+}
+
+/// Tokenize a string that isn't part of a [`SourceFile`]. Offsets start at 0.
+///
+/// ```
+/// use lexer::{tokenize_str, TokenData};
+///
+/// let tokens = tokenize_str("\\x -> x").unwrap();
+/// assert_eq!(tokens[1].data, TokenData::Ident("x"));
+/// ```
+pub fn tokenize_str(content: &str) -> LexerResult<Vec<Token<'_>>> {
+    Lexer::from_str(content, Offset(0)).tokenize()
+}
+
+/// Load a file into `src_files` and tokenize it.
+///
+/// The tokens borrow from `src_files`, which stays mutably borrowed for as long as they're alive, so
+/// a shared reference to it is returned alongside them for reporting errors.
+pub fn lex_file<'files>(
+    src_files: &'files mut SourceFiles,
+    path: &Path,
+) -> Result<(&'files SourceFiles, Vec<Token<'files>>), errors::Error> {
+    let (offset, _) = src_files.load_source_file(path);
+    let src_files: &'files SourceFiles = src_files;
+    match Lexer::from_source_file(src_files.get_by_offset(offset)).tokenize() {
+        Result::Ok(tokens) => Result::Ok((src_files, tokens)),
+        Result::Err(err) => Result::Err(err.reportable()),
+    }
+}
+
+/// Reconstruct the source text that `tokens` were lexed from. `src` must be the file the tokens
+/// were lexed from.
+///
+/// The lexer is lossless, so for a complete token stream this is exactly the file's content.
+/// `Indent`, `Dedent` and `Eof` are synthesized by the lexer and contribute nothing.
+pub fn tokens_to_source(tokens: &[Token], src: &SourceFile) -> String {
+    let mut result = String::new();
+    for token in tokens {
+        match token.data {
+            TokenData::Backslash => result.push('\\'),
+            TokenData::LParen => result.push('('),
+            TokenData::RParen => result.push(')'),
+            TokenData::Equals => result.push('='),
+            TokenData::Minus => result.push('-'),
+            TokenData::Colon => result.push(':'),
+            TokenData::Comma => result.push(','),
+            TokenData::Let => result.push_str("let"),
+            // `RArrow` can be written `->` or `=>`
+            TokenData::RArrow
+            | TokenData::Space
+            | TokenData::Newline
+            | TokenData::Comment(_)
+            | TokenData::Ident(_) => result.push_str(src.slice(token.span)),
+            TokenData::Indent | TokenData::Dedent | TokenData::Eof => {}
+        }
+    }
+    result
+}
+
+/// Find the comments that document definitions.
+///
+/// A definition is an identifier followed by `=`. The comments that immediately precede it
+/// (separated only by spaces and newlines) are its documentation. Only comments that start a
+/// line count, so a comment at the end of the previous line isn't attached to the next
+/// definition. Each comment is returned alongside the span of the identifier it documents, with
+/// surrounding whitespace trimmed.
+pub fn doc_comments<'src>(tokens: &[Token<'src>]) -> Vec<(Span, &'src str)> {
+    let mut result = Vec::new();
+    let mut comments: Vec<&'src str> = Vec::new();
+    let mut at_line_start = true;
+    for (ix, token) in tokens.iter().enumerate() {
+        let after_line_start = at_line_start;
+        at_line_start = false;
+        match token.data {
+            TokenData::Space => at_line_start = after_line_start,
+            TokenData::Newline => at_line_start = true,
+            TokenData::Comment(text) if after_line_start => comments.push(text.trim()),
+            TokenData::Ident(_) => {
+                let is_definition = tokens[ix + 1..]
+                    .iter()
+                    .find(|token| token.data != TokenData::Space)
+                    .is_some_and(|token| token.data == TokenData::Equals);
+                if is_definition {
+                    for comment in comments.iter() {
+                        result.push((token.span, *comment));
+                    }
+                }
+                comments.clear();
+            }
+            _ => comments.clear(),
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 fn test_source_file(content: String) -> SourceFile {
     SourceFile {
@@ -381,7 +969,7 @@ fn test_lexer_example3() {
                 data: TokenData::Eof,
                 span: Span {
                     start: Offset(19),
-                    length: Offset(1)
+                    length: Offset(0)
                 }
             },
         ])
@@ -442,7 +1030,7 @@ fn test_lexer_example5() {
                 data: TokenData::Eof,
                 span: Span {
                     start: Offset(7),
-                    length: Offset(1)
+                    length: Offset(0)
                 }
             },
         ])
@@ -457,3 +1045,846 @@ fn test_lexer_example6() {
         Result::Err(Error::Unexpected('', Offset(6)))
     );
 }
+
+#[test]
+fn test_lexer_colon() {
+    let src_file = test_source_file(String::from("x : a"));
+    assert_eq!(
+        Lexer::from_source_file(&src_file).tokenize(),
+        Result::Ok(vec![
+            Token::new(TokenData::Ident("x"), 0, 1),
+            Token::new(TokenData::Space, 1, 1),
+            Token::new(TokenData::Colon, 2, 1),
+            Token::new(TokenData::Space, 3, 1),
+            Token::new(TokenData::Ident("a"), 4, 1),
+            Token::new(TokenData::Eof, 5, 0),
+        ])
+    )
+}
+
+#[test]
+fn test_lexer_rarrow_span() {
+    let tokens = tokenize_str("->").unwrap();
+    assert_eq!(tokens[0].data, TokenData::RArrow);
+    assert_eq!(tokens[0].span, Span::new(Offset(0), Offset(2)));
+
+    let tokens = tokenize_str("- x").unwrap();
+    assert_eq!(tokens[0].data, TokenData::Minus);
+    assert_eq!(tokens[0].span, Span::new(Offset(0), Offset(1)));
+}
+
+#[test]
+fn test_lexer_fat_arrow() {
+    assert_eq!(
+        tokenize_str("=>").unwrap(),
+        vec![
+            Token::new(TokenData::RArrow, 0, 2),
+            Token::new(TokenData::Eof, 2, 0)
+        ]
+    );
+    assert_eq!(
+        tokenize_str("=").unwrap(),
+        vec![
+            Token::new(TokenData::Equals, 0, 1),
+            Token::new(TokenData::Eof, 1, 0)
+        ]
+    );
+    assert_eq!(
+        tokenize_str("==>").unwrap(),
+        vec![
+            Token::new(TokenData::Equals, 0, 1),
+            Token::new(TokenData::RArrow, 1, 2),
+            Token::new(TokenData::Eof, 3, 0)
+        ]
+    );
+    let src_file = test_source_file(String::from("\\x => x"));
+    let tokens = Lexer::from_source_file(&src_file).tokenize().unwrap();
+    assert_eq!(tokens_to_source(&tokens, &src_file), "\\x => x")
+}
+
+#[test]
+fn test_lexer_skip_trivia() {
+    assert_eq!(
+        Lexer::from_str("a  b", Offset(0))
+            .with_skip_trivia()
+            .tokenize(),
+        Result::Ok(vec![
+            Token::new(TokenData::Ident("a"), 0, 1),
+            Token::new(TokenData::Ident("b"), 3, 1),
+            Token::new(TokenData::Eof, 4, 0),
+        ])
+    );
+    assert_eq!(
+        Lexer::from_str("a\n  b -- c", Offset(0))
+            .with_layout()
+            .with_skip_trivia()
+            .tokenize(),
+        Result::Ok(vec![
+            Token::new(TokenData::Ident("a"), 0, 1),
+            Token::new(TokenData::Indent, 4, 0),
+            Token::new(TokenData::Ident("b"), 4, 1),
+            Token::new(TokenData::Dedent, 10, 0),
+            Token::new(TokenData::Eof, 10, 0),
+        ])
+    )
+}
+
+#[test]
+fn test_lexer_comma() {
+    let src_file = test_source_file(String::from("x,y"));
+    assert_eq!(
+        Lexer::from_source_file(&src_file).tokenize(),
+        Result::Ok(vec![
+            Token::new(TokenData::Ident("x"), 0, 1),
+            Token::new(TokenData::Comma, 1, 1),
+            Token::new(TokenData::Ident("y"), 2, 1),
+            Token::new(TokenData::Eof, 3, 0),
+        ])
+    )
+}
+
+#[test]
+fn test_lexer_max_ident_len1() {
+    let src_file = test_source_file(String::from("abc abcdef"));
+    assert_eq!(
+        Lexer::from_source_file(&src_file)
+            .with_max_ident_len(3)
+            .tokenize(),
+        Result::Err(Error::IdentTooLong(3, Offset(7)))
+    );
+}
+
+#[test]
+fn test_lexer_max_ident_len2() {
+    let src_file = test_source_file(String::from("abc"));
+    assert_eq!(
+        Lexer::from_source_file(&src_file)
+            .with_max_ident_len(3)
+            .tokenize()
+            .map(|tokens| tokens.len()),
+        Result::Ok(2)
+    );
+}
+
+#[test]
+fn test_lexer_comment1() {
+    let src_file = test_source_file(String::from("x -- hello\ny"));
+    assert_eq!(
+        Lexer::from_source_file(&src_file)
+            .tokenize()
+            .map(|tokens| tokens
+                .into_iter()
+                .map(|token| token.data)
+                .collect::<Vec<_>>()),
+        Result::Ok(vec![
+            TokenData::Ident("x"),
+            TokenData::Space,
+            TokenData::Comment(" hello"),
+            TokenData::Newline,
+            TokenData::Ident("y"),
+            TokenData::Eof
+        ])
+    );
+}
+
+#[test]
+fn test_doc_comments1() {
+    let src_file = test_source_file(String::from("-- the identity\nid = \\x -> x"));
+    let tokens = Lexer::from_source_file(&src_file).tokenize().unwrap();
+    assert_eq!(
+        doc_comments(&tokens),
+        vec![(
+            Span {
+                start: Offset(16),
+                length: Offset(2)
+            },
+            "the identity"
+        )]
+    );
+}
+
+#[test]
+fn test_doc_comments2() {
+    let src_file = test_source_file(String::from("-- not documentation\nx y"));
+    let tokens = Lexer::from_source_file(&src_file).tokenize().unwrap();
+    assert_eq!(doc_comments(&tokens), vec![]);
+
+    // A comment at the end of a line doesn't document the next definition
+    let src_file = test_source_file(String::from("x = y -- note about x\nz = w"));
+    let tokens = Lexer::from_source_file(&src_file).tokenize().unwrap();
+    assert_eq!(doc_comments(&tokens), vec![]);
+}
+
+#[test]
+fn test_lexer_nonzero_start() {
+    let src_file = SourceFile {
+        name: String::from("test"),
+        start: Offset(10),
+        content: String::from("ab cd"),
+    };
+    assert_eq!(
+        Lexer::from_source_file(&src_file)
+            .tokenize()
+            .map(|tokens| tokens
+                .into_iter()
+                .map(|token| token.data)
+                .collect::<Vec<_>>()),
+        Result::Ok(vec![
+            TokenData::Ident("ab"),
+            TokenData::Space,
+            TokenData::Ident("cd"),
+            TokenData::Eof
+        ])
+    );
+}
+
+#[test]
+fn test_lexer_unicode_ident1() {
+    let src_file = test_source_file(String::from("naïve"));
+    assert_eq!(
+        Lexer::from_source_file(&src_file).tokenize(),
+        Result::Ok(vec![
+            Token {
+                data: TokenData::Ident("naïve"),
+                span: Span {
+                    start: Offset(0),
+                    length: Offset(6)
+                }
+            },
+            Token {
+                data: TokenData::Eof,
+                span: Span {
+                    start: Offset(6),
+                    length: Offset(0)
+                }
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_lexer_unicode_ident2() {
+    let src_file = test_source_file(String::from("αβ γ"));
+    assert_eq!(
+        Lexer::from_source_file(&src_file).tokenize(),
+        Result::Ok(vec![
+            Token {
+                data: TokenData::Ident("αβ"),
+                span: Span {
+                    start: Offset(0),
+                    length: Offset(4)
+                }
+            },
+            Token {
+                data: TokenData::Space,
+                span: Span {
+                    start: Offset(4),
+                    length: Offset(1)
+                }
+            },
+            Token {
+                data: TokenData::Ident("γ"),
+                span: Span {
+                    start: Offset(5),
+                    length: Offset(2)
+                }
+            },
+            Token {
+                data: TokenData::Eof,
+                span: Span {
+                    start: Offset(7),
+                    length: Offset(0)
+                }
+            },
+        ])
+    );
+}
+
+#[test]
+fn test_tokenize_str1() {
+    assert_eq!(
+        tokenize_str("f = x"),
+        Result::Ok(vec![
+            Token {
+                data: TokenData::Ident("f"),
+                span: Span {
+                    start: Offset(0),
+                    length: Offset(1)
+                }
+            },
+            Token {
+                data: TokenData::Space,
+                span: Span {
+                    start: Offset(1),
+                    length: Offset(1)
+                }
+            },
+            Token {
+                data: TokenData::Equals,
+                span: Span {
+                    start: Offset(2),
+                    length: Offset(1)
+                }
+            },
+            Token {
+                data: TokenData::Space,
+                span: Span {
+                    start: Offset(3),
+                    length: Offset(1)
+                }
+            },
+            Token {
+                data: TokenData::Ident("x"),
+                span: Span {
+                    start: Offset(4),
+                    length: Offset(1)
+                }
+            },
+            Token {
+                data: TokenData::Eof,
+                span: Span {
+                    start: Offset(5),
+                    length: Offset(0)
+                }
+            }
+        ])
+    )
+}
+
+#[test]
+fn test_token_type_all1() {
+    for (ix, tt) in TokenType::all().iter().enumerate() {
+        assert_eq!(tt.to_usize(), ix);
+        assert_eq!(&TokenType::unsafe_from_usize(ix), tt);
+    }
+}
+
+#[test]
+fn test_lexer_let() {
+    assert_eq!(
+        tokenize_str("let letter").unwrap(),
+        vec![
+            Token::new(TokenData::Let, 0, 3),
+            Token::new(TokenData::Space, 3, 1),
+            Token::new(TokenData::Ident("letter"), 4, 6),
+            Token::new(TokenData::Eof, 10, 0),
+        ]
+    )
+}
+
+#[test]
+fn test_token_type_category() {
+    let categories: Vec<(TokenType, Category)> = TokenType::all()
+        .into_iter()
+        .map(|tt| {
+            let category = tt.category();
+            (tt, category)
+        })
+        .collect();
+    assert_eq!(
+        categories,
+        vec![
+            (TokenType::Space, Category::Trivia),
+            (TokenType::Newline, Category::Trivia),
+            (TokenType::Comment, Category::Trivia),
+            (TokenType::Backslash, Category::Punctuation),
+            (TokenType::Ident, Category::Name),
+            (TokenType::RArrow, Category::Punctuation),
+            (TokenType::LParen, Category::Punctuation),
+            (TokenType::RParen, Category::Punctuation),
+            (TokenType::Equals, Category::Punctuation),
+            (TokenType::Minus, Category::Punctuation),
+            (TokenType::Colon, Category::Punctuation),
+            (TokenType::Comma, Category::Punctuation),
+            (TokenType::Let, Category::Keyword),
+            (TokenType::Indent, Category::Layout),
+            (TokenType::Dedent, Category::Layout),
+            (TokenType::Eof, Category::Eof),
+        ]
+    )
+}
+
+#[test]
+fn test_token_is_trivia() {
+    let src_file = test_source_file(String::from("f -- comment\n  (\\x -> x) = y"));
+    let tokens = Lexer::from_source_file(&src_file).tokenize().unwrap();
+    let trivia: Vec<(TokenType, bool)> = tokens
+        .iter()
+        .map(|token| (token.token_type(), token.is_trivia()))
+        .collect();
+    assert_eq!(
+        trivia,
+        vec![
+            (TokenType::Ident, false),
+            (TokenType::Space, true),
+            (TokenType::Comment, true),
+            (TokenType::Newline, true),
+            (TokenType::Space, true),
+            (TokenType::Space, true),
+            (TokenType::LParen, false),
+            (TokenType::Backslash, false),
+            (TokenType::Ident, false),
+            (TokenType::Space, true),
+            (TokenType::RArrow, false),
+            (TokenType::Space, true),
+            (TokenType::Ident, false),
+            (TokenType::RParen, false),
+            (TokenType::Space, true),
+            (TokenType::Equals, false),
+            (TokenType::Space, true),
+            (TokenType::Ident, false),
+            (TokenType::Eof, false),
+        ]
+    );
+    assert_eq!(
+        significant(&tokens)
+            .map(|token| token.data)
+            .collect::<Vec<TokenData>>(),
+        vec![
+            TokenData::Ident("f"),
+            TokenData::LParen,
+            TokenData::Backslash,
+            TokenData::Ident("x"),
+            TokenData::RArrow,
+            TokenData::Ident("x"),
+            TokenData::RParen,
+            TokenData::Equals,
+            TokenData::Ident("y"),
+            TokenData::Eof,
+        ]
+    )
+}
+
+#[test]
+fn test_token_type_ord1() {
+    let mut tts = vec![
+        TokenType::Eof,
+        TokenType::Ident,
+        TokenType::Space,
+        TokenType::RParen,
+        TokenType::Backslash,
+    ];
+    tts.sort();
+    assert_eq!(
+        tts,
+        vec![
+            TokenType::Space,
+            TokenType::Backslash,
+            TokenType::Ident,
+            TokenType::RParen,
+            TokenType::Eof,
+        ]
+    );
+
+    let all = TokenType::all();
+    for (a, b) in all.iter().zip(all.iter().skip(1)) {
+        assert!(a < b);
+        assert_eq!(a.cmp(b), a.to_usize().cmp(&b.to_usize()));
+    }
+
+    let set: std::collections::BTreeSet<TokenType> =
+        vec![TokenType::Equals, TokenType::LParen, TokenType::Equals]
+            .into_iter()
+            .collect();
+    assert_eq!(
+        set.into_iter().collect::<Vec<TokenType>>(),
+        vec![TokenType::LParen, TokenType::Equals]
+    )
+}
+
+#[test]
+fn test_lexer_layout1() {
+    let src_file = test_source_file(String::from("a\n  b\n    c\n\n  d\ne"));
+    assert_eq!(
+        Lexer::from_source_file(&src_file)
+            .with_layout()
+            .tokenize()
+            .map(|tokens| tokens
+                .into_iter()
+                .filter(|token| token.is_significant())
+                .map(|token| token.data)
+                .collect::<Vec<TokenData>>()),
+        Result::Ok(vec![
+            TokenData::Ident("a"),
+            TokenData::Indent,
+            TokenData::Ident("b"),
+            TokenData::Indent,
+            TokenData::Ident("c"),
+            TokenData::Dedent,
+            TokenData::Ident("d"),
+            TokenData::Dedent,
+            TokenData::Ident("e"),
+            TokenData::Eof
+        ])
+    )
+}
+
+#[test]
+fn test_lexer_layout2() {
+    let src_file = test_source_file(String::from("a\n  b\n    c"));
+    let tokens = Lexer::from_source_file(&src_file)
+        .with_layout()
+        .tokenize()
+        .unwrap();
+    let end = Span {
+        start: Offset(11),
+        length: Offset(0),
+    };
+    assert_eq!(
+        &tokens[tokens.len() - 3..],
+        &[
+            Token {
+                data: TokenData::Dedent,
+                span: end
+            },
+            Token {
+                data: TokenData::Dedent,
+                span: end
+            },
+            Token {
+                data: TokenData::Eof,
+                span: Span {
+                    start: Offset(11),
+                    length: Offset(0)
+                }
+            }
+        ]
+    )
+}
+
+#[test]
+fn test_lexer_layout3() {
+    let src_file = test_source_file(String::from("a\n    b\n  c"));
+    assert_eq!(
+        Lexer::from_source_file(&src_file).with_layout().tokenize(),
+        Result::Err(Error::BadDedent(Offset(10)))
+    )
+}
+
+#[test]
+fn test_lexer_recovering1() {
+    let src_file = test_source_file(String::from("a ? b"));
+    let (tokens, errors) = Lexer::from_source_file(&src_file).tokenize_recovering(10);
+    assert_eq!(
+        tokens
+            .into_iter()
+            .map(|token| token.data)
+            .collect::<Vec<TokenData>>(),
+        vec![
+            TokenData::Ident("a"),
+            TokenData::Space,
+            TokenData::Space,
+            TokenData::Ident("b"),
+            TokenData::Eof
+        ]
+    );
+    assert_eq!(errors, vec![Error::Unexpected('?', Offset(2))])
+}
+
+#[test]
+fn test_lexer_recovering_unlimited() {
+    let (tokens, errors) = Lexer::from_str("?", Offset(0)).tokenize_recovering(usize::MAX);
+    assert_eq!(tokens, vec![Token::new(TokenData::Eof, 1, 0)]);
+    assert_eq!(errors, vec![Error::Unexpected('?', Offset(0))])
+}
+
+#[test]
+fn test_lexer_recovering2() {
+    let src_file = test_source_file(String::from("? ? ? ? ? ?"));
+    let (tokens, errors) = Lexer::from_source_file(&src_file).tokenize_recovering(3);
+    assert_eq!(
+        errors,
+        vec![
+            Error::Unexpected('?', Offset(0)),
+            Error::Unexpected('?', Offset(2)),
+            Error::Unexpected('?', Offset(4)),
+            Error::TooManyErrors(Offset(6))
+        ]
+    );
+    assert_eq!(
+        tokens.last().map(|token| token.data),
+        Option::Some(TokenData::Eof)
+    )
+}
+
+#[cfg(test)]
+fn token_data(input: &str) -> Vec<TokenData<'_>> {
+    tokenize_str(input)
+        .unwrap()
+        .into_iter()
+        .map(|token| token.data)
+        .collect()
+}
+
+#[test]
+fn test_lexer_minus1() {
+    assert_eq!(
+        token_data("a - b"),
+        vec![
+            TokenData::Ident("a"),
+            TokenData::Space,
+            TokenData::Minus,
+            TokenData::Space,
+            TokenData::Ident("b"),
+            TokenData::Eof
+        ]
+    )
+}
+
+#[test]
+fn test_lexer_minus2() {
+    assert_eq!(
+        tokenize_str("a-b"),
+        Result::Ok(vec![
+            Token {
+                data: TokenData::Ident("a"),
+                span: Span {
+                    start: Offset(0),
+                    length: Offset(1)
+                }
+            },
+            Token {
+                data: TokenData::Minus,
+                span: Span {
+                    start: Offset(1),
+                    length: Offset(1)
+                }
+            },
+            Token {
+                data: TokenData::Ident("b"),
+                span: Span {
+                    start: Offset(2),
+                    length: Offset(1)
+                }
+            },
+            Token {
+                data: TokenData::Eof,
+                span: Span {
+                    start: Offset(3),
+                    length: Offset(0)
+                }
+            }
+        ])
+    )
+}
+
+#[test]
+fn test_lexer_minus3() {
+    assert_eq!(
+        token_data("a->b"),
+        vec![
+            TokenData::Ident("a"),
+            TokenData::RArrow,
+            TokenData::Ident("b"),
+            TokenData::Eof
+        ]
+    )
+}
+
+#[test]
+fn test_lexer_minus4() {
+    assert_eq!(
+        token_data("a -"),
+        vec![
+            TokenData::Ident("a"),
+            TokenData::Space,
+            TokenData::Minus,
+            TokenData::Eof
+        ]
+    )
+}
+
+#[test]
+fn test_lexer_from_str1() {
+    assert_eq!(
+        Lexer::from_str("hello", Offset(100)).tokenize(),
+        Result::Ok(vec![
+            Token {
+                data: TokenData::Ident("hello"),
+                span: Span {
+                    start: Offset(100),
+                    length: Offset(5)
+                }
+            },
+            Token {
+                data: TokenData::Eof,
+                span: Span {
+                    start: Offset(105),
+                    length: Offset(0)
+                }
+            }
+        ])
+    )
+}
+
+#[test]
+fn test_lex_file1() {
+    let mut src_files = SourceFiles::new();
+    match lex_file(&mut src_files, Path::new("test_files/good.spd")) {
+        Result::Ok((_, tokens)) => assert_eq!(
+            tokens
+                .iter()
+                .filter(|token| token.is_significant())
+                .map(|token| token.data)
+                .collect::<Vec<TokenData>>(),
+            vec![
+                TokenData::Ident("id"),
+                TokenData::Equals,
+                TokenData::Backslash,
+                TokenData::Ident("x"),
+                TokenData::RArrow,
+                TokenData::Ident("x"),
+                TokenData::Eof
+            ]
+        ),
+        Result::Err(err) => panic!("{}", err.message),
+    }
+}
+
+#[test]
+fn test_lex_file2() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(String::from("other"), String::from("abc"));
+    match lex_file(&mut src_files, Path::new("test_files/bad.spd")) {
+        Result::Ok((_, tokens)) => panic!("expected an error, got {:?}", tokens),
+        Result::Err(err) => {
+            assert_eq!(err.message, "Unexpected symbol '?'");
+            // `other` takes up offsets 0 to 3, and the next file starts after a one-byte gap.
+            assert_eq!(err.highlight.start(), Offset(17));
+        }
+    }
+}
+
+#[test]
+fn test_lexer_wildcard() {
+    assert_eq!(
+        token_data("\\_ -> x"),
+        vec![
+            TokenData::Backslash,
+            TokenData::Ident("_"),
+            TokenData::Space,
+            TokenData::RArrow,
+            TokenData::Space,
+            TokenData::Ident("x"),
+            TokenData::Eof
+        ]
+    )
+}
+
+#[test]
+fn test_lexer_positions1() {
+    let src_file = test_source_file(String::from("  aa\x7f"));
+    let err = Lexer::from_source_file(&src_file)
+        .with_positions()
+        .tokenize()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        Error::Positioned {
+            line: 1,
+            column: 5,
+            error: Box::new(Error::Unexpected('\x7f', Offset(4)))
+        }
+    );
+    assert_eq!(
+        err.message_with_position(),
+        "line 1, col 5: Unexpected symbol '\x7f'"
+    )
+}
+
+#[test]
+fn test_lexer_positions2() {
+    let src_file = test_source_file(String::from("a\n  \u{3bb} ?"));
+    let err = Lexer::from_source_file(&src_file)
+        .with_positions()
+        .tokenize()
+        .unwrap_err();
+    assert_eq!(
+        err.message_with_position(),
+        "line 2, col 5: Unexpected symbol '?'"
+    )
+}
+
+#[test]
+fn test_lexer_positions3() {
+    let src_file = test_source_file(String::from("  aa\x7f"));
+    let err = Lexer::from_source_file(&src_file).tokenize().unwrap_err();
+    assert_eq!(err.message_with_position(), "Unexpected symbol '\x7f'")
+}
+
+#[test]
+fn test_token_text() {
+    let src_file = test_source_file(String::from("\\abc -> abc"));
+    let tokens = Lexer::from_source_file(&src_file).tokenize().unwrap();
+    assert_eq!(tokens[0].data, TokenData::Backslash);
+    assert_eq!(tokens[0].text(&src_file), "\\");
+    assert_eq!(tokens[1].data, TokenData::Ident("abc"));
+    assert_eq!(tokens[1].text(&src_file), "abc");
+    assert_eq!(tokens.last().unwrap().text(&src_file), "")
+}
+
+#[test]
+fn test_tokens_to_source1() {
+    let content = String::from("f = \\input -> input");
+    let src_file = test_source_file(content.clone());
+    let tokens = Lexer::from_source_file(&src_file).tokenize().unwrap();
+    assert_eq!(tokens_to_source(&tokens, &src_file), content)
+}
+
+#[test]
+fn test_tokens_to_source2() {
+    let content = String::from("-- id\nid =\n  \\x -> x\nconst = \\x -> \\y -> (x)\n");
+    let src_file = test_source_file(content.clone());
+    let tokens = Lexer::from_source_file(&src_file)
+        .with_layout()
+        .tokenize()
+        .unwrap();
+    assert_eq!(tokens_to_source(&tokens, &src_file), content)
+}
+
+#[test]
+fn test_significant() {
+    let tokens = Lexer::from_str("a  b\nc", Offset(0)).tokenize().unwrap();
+    assert_eq!(
+        significant(&tokens).collect::<Vec<_>>(),
+        vec![
+            &Token::new(TokenData::Ident("a"), 0, 1),
+            &Token::new(TokenData::Ident("b"), 3, 1),
+            &Token::new(TokenData::Ident("c"), 5, 1),
+            &Token::new(TokenData::Eof, 6, 0),
+        ]
+    )
+}
+
+#[test]
+fn test_token_new() {
+    assert_eq!(
+        Token::new(TokenData::Backslash, 2, 1),
+        Token {
+            data: TokenData::Backslash,
+            span: Span {
+                start: Offset(2),
+                length: Offset(1)
+            }
+        }
+    )
+}
+
+#[test]
+fn test_error_into_reportable() {
+    let err: errors::Error = Error::Unexpected('?', Offset(3)).into();
+    assert_eq!(err.message, "Unexpected symbol '?'")
+}
+
+#[test]
+fn test_error_boxed() {
+    let err: Box<dyn std::error::Error> = Box::new(Error::Unexpected('?', Offset(3)));
+    assert_eq!(err.to_string(), "Unexpected symbol '?'");
+
+    let err: Box<dyn std::error::Error> = Lexer::from_str("x\n ?", Offset(0))
+        .with_positions()
+        .tokenize()
+        .unwrap_err()
+        .into();
+    assert_eq!(err.to_string(), "line 2, col 2: Unexpected symbol '?'")
+}
@@ -1,43 +1,118 @@
+pub mod program;
+
 #[cfg(test)]
 use ast::syntax::Expr;
-use ast::syntax::{ExprBuilder, ExprRef};
+use ast::syntax::{Definition, ExprBuilder, ExprRef};
+#[cfg(test)]
+use ast::types::Type;
+use ast::types::TypeRef;
 use bit_set::BitSet;
-use errors::Highlight;
+use errors::{Diagnostic, Highlight};
 use lazy_static::lazy_static;
 #[cfg(test)]
 use lexer::Lexer;
-use lexer::{Token, TokenData, TokenType};
+use lexer::{tokenize_str, Token, TokenData, TokenType};
 use span::Offset;
 #[cfg(test)]
-use span::{SourceFile, Span};
+use span::SourceFile;
+use span::Span;
 use std::fmt::{Debug, Display};
 use std::slice::Iter;
 
 #[derive(Debug, PartialEq, Eq)]
-pub enum Error<'src, 'tokens> {
+pub enum Error<'src> {
     UnexpectedEof(Offset),
+    /// The opening parenthesis that went past [`ParserConfig::max_paren_depth`].
+    TooDeeplyNested(Span),
     Unexpected {
-        actual: &'tokens Token<'src>,
+        actual: Token<'src>,
         expected: ExpectedSet,
     },
+    /// A recovering parser found more errors than it was allowed to report, and stopped here.
+    TooManyErrors(Offset),
 }
 
-impl<'src, 'tokens> Error<'src, 'tokens> {
-    pub fn reportable(&self) -> errors::Error {
+impl<'src> Error<'src> {
+    /// Where the error starts.
+    pub fn offset(&self) -> Offset {
         match self {
-            Error::UnexpectedEof(offset) => errors::Error {
-                highlight: Highlight::Point(*offset),
-                message: String::from("Unexpected end of input"),
-            },
+            Error::UnexpectedEof(offset) | Error::TooManyErrors(offset) => *offset,
+            Error::TooDeeplyNested(span) => span.start,
+            Error::Unexpected { actual, .. } => actual.span.start,
+        }
+    }
+
+    pub fn reportable(&self) -> Diagnostic {
+        match self {
+            Error::UnexpectedEof(offset) => errors::Error::at(Highlight::Point(*offset))
+                .message("Unexpected end of input")
+                .into(),
+
+            Error::TooDeeplyNested(span) => errors::Error::at(Highlight::Span(*span))
+                .message("Parentheses are nested too deeply")
+                .into(),
+
+            Error::TooManyErrors(offset) => errors::Error::at(Highlight::Point(*offset))
+                .message("Too many errors; giving up")
+                .into(),
 
-            Error::Unexpected { actual, expected } => errors::Error {
-                highlight: Highlight::Span(actual.span),
-                message: format!(
+            Error::Unexpected { actual, expected } => {
+                let err = errors::Error::at(Highlight::Span(actual.span)).message(format!(
                     "Unexpected {}, expecting one of: {}",
                     actual.token_type(),
                     expected
-                ),
-            },
+                ));
+                // A keyword where a name was expected looks like a perfectly good name to
+                // someone who doesn't know it's reserved.
+                match actual.token_type().keyword() {
+                    Option::Some(keyword) if expected.contains(&TokenType::Ident) => {
+                        err.note(format!("`{}` is a reserved keyword", keyword))
+                    }
+                    _ => err.into(),
+                }
+            }
+        }
+    }
+}
+
+impl<'src> From<Error<'src>> for Diagnostic {
+    fn from(err: Error<'src>) -> Self {
+        err.reportable()
+    }
+}
+
+impl<'src> Display for Error<'src> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        formatter.write_str(&self.reportable().error.message)
+    }
+}
+
+impl<'src> std::error::Error for Error<'src> {}
+
+/// A parse or lex error that doesn't borrow from the source. Errors that borrow can only be boxed
+/// as a `Box<dyn std::error::Error + 'src>`, so this is for passing errors to code that needs
+/// them to be `'static`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedError {
+    pub message: String,
+    /// Where the error starts.
+    pub offset: Offset,
+}
+
+impl Display for OwnedError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        formatter.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for OwnedError {}
+
+impl<'src> From<Error<'src>> for OwnedError {
+    fn from(err: Error<'src>) -> Self {
+        let reportable = err.reportable().error;
+        OwnedError {
+            message: reportable.message,
+            offset: reportable.highlight.start(),
         }
     }
 }
@@ -79,10 +154,14 @@ impl ExpectedSet {
         self.bits.contains(tt.to_usize())
     }
 
+    /// The token types in the set, in ascending `TokenType::to_usize` order. Error messages
+    /// depend on this order being stable.
     pub fn as_vec(&self) -> Vec<TokenType> {
-        self.bits
-            .iter()
-            .map(|i| TokenType::unsafe_from_usize(i))
+        let mut indices: Vec<usize> = self.bits.iter().collect();
+        indices.sort_unstable();
+        indices
+            .into_iter()
+            .map(TokenType::unsafe_from_usize)
             .collect()
     }
 }
@@ -199,19 +278,57 @@ macro_rules! with_follows_extended {
     }};
 }
 
-pub type ParseResult<'src, 'tokens, T> = Result<T, Error<'src, 'tokens>>;
+pub type ParseResult<'src, T> = Result<T, Error<'src>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParserConfig {
+    /// Whether `f x` is an application. When it's `false`, an expression that is followed by
+    /// another atom is an error. Experimental.
+    pub allow_juxtaposition: bool,
+    /// How many parentheses can be open at once. Parentheses are parsed recursively, so this
+    /// stops adversarial input from overflowing the stack. The default, 256, is well within a
+    /// 2 MiB thread stack in a debug build.
+    pub max_paren_depth: usize,
+    /// Whether a parenthesised expression is wrapped in a `Parens` node. They're only needed to
+    /// print the expression the way it was written, so tools that don't care about formatting
+    /// can turn this off and get the inner expression instead.
+    pub keep_parens: bool,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig {
+            allow_juxtaposition: true,
+            max_paren_depth: 256,
+            keep_parens: true,
+        }
+    }
+}
 
 pub struct Parser<'src, 'tokens, 'builder, 'expr> {
     builder: &'builder ExprBuilder<'src, 'expr>,
+    input: &'tokens [Token<'src>],
     current: Option<&'tokens Token<'src>>,
+    /// Stands in for the current token once the input has run out, so that input without a
+    /// trailing `Eof` is treated as if it had one. It's just past the last token.
+    eof: Token<'src>,
     position: Iter<'tokens, Token<'src>>,
     expected: ExpectedSet,
     follows: Vec<ExpectedSet>,
+    /// End expressions at a line break that's followed by an unindented line. Set while parsing
+    /// the body of a definition, because the unindented line starts the next one.
+    stop_at_definition: bool,
+    config: ParserConfig,
+    /// How many parentheses are open.
+    paren_depth: usize,
 }
 
 lazy_static! {
     static ref EXPECTED_RPAREN: ExpectedSet = expected![&TokenType::RParen];
+    static ref EXPECTED_COLON: ExpectedSet = expected![&TokenType::Colon];
+    static ref EXPECTED_RARROW: ExpectedSet = expected![&TokenType::RArrow];
     static ref ATOM_START_SET: ExpectedSet = expected![&TokenType::Ident, &TokenType::LParen];
+    static ref DEFINITION_FOLLOW_SET: ExpectedSet = expected![&TokenType::Newline, &TokenType::Eof];
 }
 
 impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
@@ -219,26 +336,56 @@ impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
     pub fn new(
         builder: &'builder ExprBuilder<'src, 'expr>,
         input: &'tokens Vec<Token<'src>>,
+    ) -> Self {
+        Parser::with_config(builder, input, ParserConfig::default())
+    }
+
+    /// `input` must be terminated by a `TokenType::Eof`
+    pub fn with_config(
+        builder: &'builder ExprBuilder<'src, 'expr>,
+        input: &'tokens Vec<Token<'src>>,
+        config: ParserConfig,
     ) -> Self {
         let expected = ExpectedSet::new();
         let follows = Vec::new();
         let mut position = input.iter();
         let current = position.next();
+        let eof_offset = match input.last() {
+            Option::None => Offset(0),
+            Option::Some(token) => token.span.end(),
+        };
+        let eof = Token::new(TokenData::Eof, eof_offset.to_u32(), 0);
 
         Parser {
             builder,
+            input,
             current,
+            eof,
             position,
             expected,
             follows,
+            stop_at_definition: false,
+            config,
+            paren_depth: 0,
+        }
+    }
+
+    /// The tokens that haven't been consumed yet, starting with the current token.
+    pub fn remaining(&self) -> &'tokens [Token<'src>] {
+        match self.current {
+            Option::None => &[],
+            Option::Some(_) => {
+                let after_current = self.position.as_slice().len();
+                &self.input[self.input.len() - after_current - 1..]
+            }
         }
     }
 
     #[inline]
-    fn current_token(&self) -> &'tokens Token<'src> {
+    fn current_token(&self) -> Token<'src> {
         match self.current {
-            Option::Some(token) => token,
-            Option::None => panic!("current_token failed: ran out of input"),
+            Option::Some(token) => *token,
+            Option::None => self.eof,
         }
     }
 
@@ -249,23 +396,19 @@ impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
         res
     }
 
-    fn expect(&mut self, tt: &'tokens TokenType) -> Option<&'tokens Token<'src>> {
+    fn expect(&mut self, tt: &'tokens TokenType) -> Option<Token<'src>> {
         self.expected.insert(tt);
         let token = self.current_token();
         if token.token_type() == *tt {
-            match self.consume() {
-                Option::Some(_) => {
-                    self.expected.clear();
-                }
-                Option::None => (),
-            }
+            let _ = self.consume();
+            self.expected.clear();
             Option::Some(token)
         } else {
             Option::None
         }
     }
 
-    fn unexpected_with<T>(&self, extra: &ExpectedSet) -> ParseResult<'src, 'tokens, T> {
+    fn unexpected_with<T>(&self, extra: &ExpectedSet) -> ParseResult<'src, T> {
         let actual = self.current_token();
         let mut expected = self.expected.clone();
         expected.union(extra);
@@ -273,7 +416,7 @@ impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
     }
 
     #[inline]
-    fn unexpected<T>(&mut self) -> ParseResult<'src, 'tokens, T> {
+    fn unexpected<T>(&mut self) -> ParseResult<'src, T> {
         self.unexpected_with(&ExpectedSet::new())
     }
 
@@ -285,57 +428,144 @@ impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
             })
     }
 
-    fn require(
-        &mut self,
-        tt: &'tokens TokenType,
-    ) -> ParseResult<'src, 'tokens, &'tokens Token<'src>> {
+    fn require(&mut self, tt: &'tokens TokenType) -> ParseResult<'src, Token<'src>> {
         match self.expect(tt) {
             Option::Some(token) => Result::Ok(token),
             Option::None => self.unexpected(),
         }
     }
 
-    fn require_ident(&mut self) -> ParseResult<'src, 'tokens, &'src str> {
+    fn require_ident(&mut self) -> ParseResult<'src, &'src str> {
         match self.expect_ident() {
             Option::Some(ident) => Result::Ok(ident),
             Option::None => self.unexpected(),
         }
     }
 
-    fn ignore_spaces(&mut self) -> usize {
+    /// Whether the current token is a line break, and the next significant token is at the
+    /// start of a line. Blank lines and comment lines in between are skipped.
+    fn at_unindented_line(&self) -> bool {
+        if self.current_token().data != TokenData::Newline {
+            return false;
+        }
+        let mut previous = self.current_token();
+        for token in self.position.as_slice() {
+            if token.is_significant() {
+                return previous.data == TokenData::Newline;
+            }
+            previous = *token;
+        }
+        false
+    }
+
+    /// Skip spaces and comments, but not line breaks.
+    fn ignore_inline_space(&mut self) -> usize {
         let mut count = 0;
-        while let TokenData::Space | TokenData::Newline = self.current_token().data {
+        while let TokenData::Space | TokenData::Comment(_) = self.current_token().data {
             let _ = self.consume();
             count += 1;
         }
         count
     }
 
+    /// Skip spaces, comments and line breaks.
+    fn ignore_whitespace(&mut self) -> usize {
+        let mut count = 0;
+        while self.current_token().is_trivia() {
+            let _ = self.consume();
+            count += 1;
+        }
+        count
+    }
+
+    /// Skip the whitespace after a token that can end an expression. Line breaks are skipped
+    /// too, unless they end the body of a definition.
+    fn ignore_space_in_expr(&mut self) {
+        let _ = self.ignore_inline_space();
+        if !(self.stop_at_definition && self.at_unindented_line()) {
+            let _ = self.ignore_whitespace();
+        }
+    }
+
+    /// Run `parse` inside a pair of parentheses whose opening `(` is `lparen`, failing if that
+    /// would exceed the configured depth.
+    fn nested<T, F>(&mut self, lparen: &Token<'src>, parse: F) -> ParseResult<'src, T>
+    where
+        F: FnOnce(&mut Self) -> ParseResult<'src, T>,
+    {
+        if self.paren_depth >= self.config.max_paren_depth {
+            return Result::Err(Error::TooDeeplyNested(lparen.span));
+        }
+        self.paren_depth += 1;
+        let res = parse(self);
+        self.paren_depth -= 1;
+        res
+    }
+
+    /// ```ignore
+    /// separated(item, sep) ::=
+    ///   [item (sep item)*]
+    /// ```
+    ///
+    /// If the first `item` fails without consuming any tokens, the list is empty. The tokens it
+    /// expected are kept, so they're reported by whatever fails next. After a separator an item
+    /// is required, so a trailing separator reports whatever `item` expected. `item` is
+    /// responsible for skipping the whitespace after itself.
+    pub fn parse_separated<T, F>(
+        &mut self,
+        sep: &'tokens TokenType,
+        mut item: F,
+    ) -> ParseResult<'src, Vec<T>>
+    where
+        F: FnMut(&mut Self) -> ParseResult<'src, T>,
+    {
+        let mut followed_by = ExpectedSet::new();
+        followed_by.insert(sep);
+
+        let mut items = Vec::new();
+        let remaining = self.remaining().len();
+        match with_follows_extended!(self, &followed_by, { item(self) }) {
+            Result::Ok(first) => items.push(first),
+            Result::Err(_) if self.remaining().len() == remaining => return Result::Ok(items),
+            Result::Err(err) => return Result::Err(err),
+        }
+        while self.expect(sep).is_some() {
+            let _ = self.ignore_whitespace();
+            items.push(with_follows_extended!(self, &followed_by, { item(self) })?);
+        }
+        Result::Ok(items)
+    }
+
     /// ```ignore
     /// atom ::=
     ///   ident
     ///   '(' expr ')'
     /// ```
-    fn try_parse_atom(&mut self) -> ParseResult<'src, 'tokens, Option<ExprRef<'src, 'expr>>>
+    fn try_parse_atom(&mut self) -> ParseResult<'src, Option<ExprRef<'src, 'expr>>>
     where
         'builder: 'expr,
     {
         match self.expect_ident() {
             Option::Some(ident) => {
-                self.ignore_spaces();
+                self.ignore_space_in_expr();
                 Result::Ok(Option::Some(self.builder.mk_ident(ident)))
             }
             Option::None => match self.expect(&TokenType::LParen) {
-                Option::Some(_) => {
-                    self.ignore_spaces();
+                Option::Some(lparen) => {
+                    let _ = self.ignore_whitespace();
 
-                    let inner =
-                        with_follows!(self, (*EXPECTED_RPAREN).clone(), { self.parse_expr() })?;
+                    let inner = self.nested(&lparen, |this| {
+                        with_follows!(this, (*EXPECTED_RPAREN).clone(), { this.parse_expr() })
+                    })?;
 
                     let _ = self.require(&TokenType::RParen)?;
-                    let _ = self.ignore_spaces();
+                    self.ignore_space_in_expr();
 
-                    Result::Ok(Option::Some(self.builder.mk_parens(inner)))
+                    Result::Ok(Option::Some(if self.config.keep_parens {
+                        self.builder.mk_parens(inner)
+                    } else {
+                        inner
+                    }))
                 }
                 Option::None => Result::Ok(Option::None),
             },
@@ -346,19 +576,19 @@ impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
     /// lambda ::=
     ///   '\' ident '->' expr
     /// ```
-    fn try_parse_lam(&mut self) -> ParseResult<'src, 'tokens, Option<ExprRef<'src, 'expr>>>
+    fn try_parse_lam(&mut self) -> ParseResult<'src, Option<ExprRef<'src, 'expr>>>
     where
         'builder: 'expr,
     {
         match self.expect(&TokenType::Backslash) {
             Option::Some(_) => {
-                let _ = self.ignore_spaces();
+                let _ = self.ignore_whitespace();
 
                 let arg = self.require_ident()?;
-                let _ = self.ignore_spaces();
+                let _ = self.ignore_whitespace();
 
                 let _ = self.require(&TokenType::RArrow)?;
-                let _ = self.ignore_spaces();
+                let _ = self.ignore_whitespace();
 
                 let body = self.parse_expr()?;
 
@@ -372,7 +602,7 @@ impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
     /// app ::=
     ///   atom atom*
     /// ```
-    fn try_parse_app(&mut self) -> ParseResult<'src, 'tokens, Option<ExprRef<'src, 'expr>>>
+    fn try_parse_app(&mut self) -> ParseResult<'src, Option<ExprRef<'src, 'expr>>>
     where
         'builder: 'expr,
     {
@@ -381,8 +611,13 @@ impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
             Option::Some(head) => {
                 let mut result = head;
                 loop {
-                    let atom_res =
-                        with_follows_extended!(self, &*ATOM_START_SET, { self.try_parse_atom() });
+                    let atom_res = if self.config.allow_juxtaposition {
+                        with_follows_extended!(self, &*ATOM_START_SET, { self.try_parse_atom() })
+                    } else {
+                        // Nothing may follow the head, so the next token has to end the
+                        // expression.
+                        Result::Ok(Option::None)
+                    };
                     match atom_res {
                         Result::Err(err) => return Result::Err(err),
                         Result::Ok(Option::None) => {
@@ -411,12 +646,75 @@ impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
         }
     }
 
+    /// ```ignore
+    /// type_atom ::=
+    ///   ident
+    ///   '(' type ')'
+    /// ```
+    fn parse_type_atom(&mut self) -> ParseResult<'src, TypeRef<'src, 'expr>>
+    where
+        'builder: 'expr,
+    {
+        match self.expect_ident() {
+            Option::Some(ident) => {
+                self.ignore_space_in_expr();
+                Result::Ok(self.builder.types().mk_tvar(ident))
+            }
+            Option::None => match self.expect(&TokenType::LParen) {
+                Option::Some(lparen) => {
+                    let _ = self.ignore_whitespace();
+
+                    let inner = self.nested(&lparen, |this| {
+                        with_follows!(this, (*EXPECTED_RPAREN).clone(), { this.parse_type() })
+                    })?;
+
+                    let _ = self.require(&TokenType::RParen)?;
+                    self.ignore_space_in_expr();
+
+                    Result::Ok(inner)
+                }
+                Option::None => self.unexpected(),
+            },
+        }
+    }
+
+    /// ```ignore
+    /// type ::=
+    ///   type_atom ['->' type]
+    /// ```
+    fn parse_type(&mut self) -> ParseResult<'src, TypeRef<'src, 'expr>>
+    where
+        'builder: 'expr,
+    {
+        let arg = with_follows_extended!(self, &*EXPECTED_RARROW, { self.parse_type_atom() })?;
+        match self.expect(&TokenType::RArrow) {
+            Option::Some(_) => {
+                let _ = self.ignore_whitespace();
+                let ret = self.parse_type()?;
+                Result::Ok(self.builder.types().mk_tarrow(arg, ret))
+            }
+            Option::None => {
+                let token = self.current_token();
+                match self.follows.last() {
+                    Option::None => self.unexpected_with(&ExpectedSet::new()),
+                    Option::Some(followed_by) => {
+                        if followed_by.contains(&token.token_type()) {
+                            Result::Ok(arg)
+                        } else {
+                            self.unexpected_with(followed_by)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     /// ```ignore
     /// expr ::=
     ///   lambda
-    ///   app
+    ///   app [':' type]
     /// ```
-    fn parse_expr(&mut self) -> ParseResult<'src, 'tokens, ExprRef<'src, 'expr>>
+    fn parse_expr(&mut self) -> ParseResult<'src, ExprRef<'src, 'expr>>
     where
         'builder: 'expr,
     {
@@ -424,21 +722,211 @@ impl<'src, 'tokens, 'builder, 'expr> Parser<'src, 'tokens, 'builder, 'expr> {
         match lam_result {
             Option::Some(expr) => Result::Ok(expr),
             Option::None => {
-                let app_result = self.try_parse_app()?;
+                let app_result =
+                    with_follows_extended!(self, &*EXPECTED_COLON, { self.try_parse_app() })?;
                 match app_result {
-                    Option::Some(expr) => Result::Ok(expr),
+                    Option::Some(expr) => match self.expect(&TokenType::Colon) {
+                        Option::Some(_) => {
+                            let _ = self.ignore_whitespace();
+                            let ty = self.parse_type()?;
+                            Result::Ok(self.builder.mk_ann(expr, ty))
+                        }
+                        Option::None => Result::Ok(expr),
+                    },
                     Option::None => self.unexpected(),
                 }
             }
         }
     }
 
-    pub fn parse_expr_eof(&mut self) -> ParseResult<'src, 'tokens, ExprRef<'src, 'expr>>
+    pub fn parse_expr_eof(&mut self) -> ParseResult<'src, ExprRef<'src, 'expr>>
     where
         'builder: 'expr,
     {
         with_follows!(self, expected![&TokenType::Eof], { self.parse_expr() })
     }
+
+    /// ```ignore
+    /// definition ::=
+    ///   ident '=' expr
+    /// ```
+    ///
+    /// The body ends at the first line that isn't indented.
+    fn parse_definition(&mut self) -> ParseResult<'src, Definition<'src, 'expr>>
+    where
+        'builder: 'expr,
+    {
+        let name = self.require_ident()?;
+        let _ = self.ignore_whitespace();
+
+        let _ = self.require(&TokenType::Equals)?;
+        let _ = self.ignore_whitespace();
+
+        self.stop_at_definition = true;
+        let body = with_follows!(self, (*DEFINITION_FOLLOW_SET).clone(), {
+            self.parse_expr()
+        });
+        self.stop_at_definition = false;
+
+        Result::Ok(Definition { name, body: body? })
+    }
+
+    /// Parse definitions one at a time, as they're requested.
+    pub fn definitions(self) -> DefinitionIterator<'src, 'tokens, 'builder, 'expr> {
+        DefinitionIterator {
+            parser: self,
+            done: false,
+        }
+    }
+
+    /// Parse every definition up to the end of the input.
+    pub fn parse_program(self) -> ParseResult<'src, Vec<Definition<'src, 'expr>>>
+    where
+        'builder: 'expr,
+    {
+        self.definitions().collect()
+    }
+
+    /// Parse every definition up to the end of the input, skipping over definitions that fail to
+    /// parse instead of stopping at the first error. After an error, parsing resumes at the next
+    /// line that isn't indented.
+    ///
+    /// At most `max_errors` errors are reported. If there are more, parsing stops at the next one,
+    /// which is reported as [`Error::TooManyErrors`].
+    pub fn parse_program_recovering(
+        mut self,
+        max_errors: usize,
+    ) -> (Vec<Definition<'src, 'expr>>, Vec<Error<'src>>)
+    where
+        'builder: 'expr,
+    {
+        let mut definitions = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            let _ = self.ignore_whitespace();
+            if self.current_token().data == TokenData::Eof {
+                break;
+            }
+            match self.parse_definition() {
+                Result::Ok(definition) => definitions.push(definition),
+                Result::Err(err) => {
+                    if errors.len() == max_errors {
+                        errors.push(Error::TooManyErrors(err.offset()));
+                        break;
+                    }
+                    errors.push(err);
+                    self.recover();
+                }
+            }
+        }
+        (definitions, errors)
+    }
+
+    /// Skip the rest of a definition that failed to parse, stopping at the line break before the
+    /// next one.
+    fn recover(&mut self) {
+        self.expected.clear();
+        self.follows.clear();
+        self.stop_at_definition = false;
+        self.paren_depth = 0;
+        while !(self.current_token().data == TokenData::Eof || self.at_unindented_line()) {
+            let _ = self.consume();
+        }
+    }
+}
+
+/// Yields each definition in the input, stopping after the first error. See
+/// [`Parser::definitions`].
+pub struct DefinitionIterator<'src, 'tokens, 'builder, 'expr> {
+    parser: Parser<'src, 'tokens, 'builder, 'expr>,
+    done: bool,
+}
+
+impl<'src, 'tokens, 'builder, 'expr> Iterator for DefinitionIterator<'src, 'tokens, 'builder, 'expr>
+where
+    'builder: 'expr,
+{
+    type Item = ParseResult<'src, Definition<'src, 'expr>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return Option::None;
+        }
+        let _ = self.parser.ignore_whitespace();
+        if self.parser.current_token().data == TokenData::Eof {
+            self.done = true;
+            return Option::None;
+        }
+        let res = self.parser.parse_definition();
+        if res.is_err() {
+            self.done = true;
+        }
+        Option::Some(res)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseOrLexError<'src> {
+    Lex(lexer::Error),
+    Parse(Error<'src>),
+}
+
+impl<'src> From<lexer::Error> for ParseOrLexError<'src> {
+    fn from(err: lexer::Error) -> Self {
+        ParseOrLexError::Lex(err)
+    }
+}
+
+impl<'src> From<Error<'src>> for ParseOrLexError<'src> {
+    fn from(err: Error<'src>) -> Self {
+        ParseOrLexError::Parse(err)
+    }
+}
+
+impl<'src> ParseOrLexError<'src> {
+    pub fn reportable(&self) -> Diagnostic {
+        match self {
+            ParseOrLexError::Lex(err) => err.reportable().into(),
+            ParseOrLexError::Parse(err) => err.reportable(),
+        }
+    }
+}
+
+impl<'src> From<ParseOrLexError<'src>> for Diagnostic {
+    fn from(err: ParseOrLexError<'src>) -> Self {
+        err.reportable()
+    }
+}
+
+impl<'src> Display for ParseOrLexError<'src> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            ParseOrLexError::Lex(err) => Display::fmt(err, formatter),
+            ParseOrLexError::Parse(err) => Display::fmt(err, formatter),
+        }
+    }
+}
+
+impl<'src> std::error::Error for ParseOrLexError<'src> {}
+
+impl<'src> From<ParseOrLexError<'src>> for OwnedError {
+    fn from(err: ParseOrLexError<'src>) -> Self {
+        let reportable = err.reportable().error;
+        OwnedError {
+            message: reportable.message,
+            offset: reportable.highlight.start(),
+        }
+    }
+}
+
+/// Lex and parse a complete expression. Offsets start at 0, as in [`lexer::tokenize_str`].
+pub fn parse_str<'src, 'expr>(
+    builder: &'expr ExprBuilder<'src, 'expr>,
+    content: &'src str,
+) -> Result<ExprRef<'src, 'expr>, ParseOrLexError<'src>> {
+    let tokens = tokenize_str(content)?;
+    let expr = Parser::new(builder, &tokens).parse_expr_eof()?;
+    Result::Ok(expr)
 }
 
 #[cfg(test)]
@@ -462,7 +950,7 @@ fn test_parser<'src, 'expr>(input: String, expected: ExprRef<'src, 'expr>) {
 }
 
 #[cfg(test)]
-fn test_parser_fail<'src, 'tokens>(input: String, expected: Error<'src, 'tokens>) {
+fn test_parser_fail<'src>(input: String, expected: Error<'src>) {
     let source_file = SourceFile {
         name: String::from("test"),
         start: Offset(0),
@@ -521,14 +1009,13 @@ fn test_parser_app_fail1() {
     test_parser_fail(
         input,
         Error::Unexpected {
-            actual: &Token {
-                data: TokenData::Backslash,
-                span: Span {
-                    start: Offset(2),
-                    length: Offset(1),
-                },
-            },
-            expected: expected![&TokenType::Ident, &TokenType::LParen, &TokenType::Eof],
+            actual: Token::new(TokenData::Backslash, 2, 1),
+            expected: expected![
+                &TokenType::Ident,
+                &TokenType::LParen,
+                &TokenType::Colon,
+                &TokenType::Eof
+            ],
         },
     )
 }
@@ -539,14 +1026,19 @@ fn test_parser_app_fail2() {
     test_parser_fail(
         input,
         Error::Unexpected {
-            actual: &Token {
+            actual: Token {
                 data: TokenData::Backslash,
                 span: Span {
                     start: Offset(3),
                     length: Offset(1),
                 },
             },
-            expected: expected![&TokenType::Ident, &TokenType::LParen, &TokenType::RParen],
+            expected: expected![
+                &TokenType::Ident,
+                &TokenType::LParen,
+                &TokenType::RParen,
+                &TokenType::Colon
+            ],
         },
     );
 }
@@ -557,14 +1049,19 @@ fn test_parser_app_fail3() {
     test_parser_fail(
         input,
         Error::Unexpected {
-            actual: &Token {
+            actual: Token {
                 data: TokenData::Backslash,
                 span: Span {
                     start: Offset(4),
                     length: Offset(1),
                 },
             },
-            expected: expected![&TokenType::Ident, &TokenType::LParen, &TokenType::Eof],
+            expected: expected![
+                &TokenType::Ident,
+                &TokenType::LParen,
+                &TokenType::Colon,
+                &TokenType::Eof
+            ],
         },
     );
 }
@@ -575,14 +1072,19 @@ fn test_parser_app_fail4() {
     test_parser_fail(
         input,
         Error::Unexpected {
-            actual: &Token {
+            actual: Token {
                 data: TokenData::Backslash,
                 span: Span {
                     start: Offset(5),
                     length: Offset(1),
                 },
             },
-            expected: expected![&TokenType::Ident, &TokenType::LParen, &TokenType::RParen],
+            expected: expected![
+                &TokenType::Ident,
+                &TokenType::LParen,
+                &TokenType::RParen,
+                &TokenType::Colon
+            ],
         },
     );
 }
@@ -592,3 +1094,483 @@ fn test_parser_parens() {
     let input = String::from("(x)");
     test_parser(input, &Expr::Parens(&Expr::Ident("x")))
 }
+
+#[test]
+fn test_parser_comment() {
+    let input = String::from("f -- the function\n  x");
+    test_parser(input, &Expr::App(&Expr::Ident("f"), &Expr::Ident("x")))
+}
+
+#[test]
+fn test_parse_str1() {
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        parse_str(&builder, "\\x -> x"),
+        Result::Ok(&Expr::Lam("x", &Expr::Ident("x")))
+    )
+}
+
+#[test]
+fn test_parse_str2() {
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        parse_str(&builder, "?"),
+        Result::Err(ParseOrLexError::Lex(lexer::Error::Unexpected(
+            '?',
+            Offset(0)
+        )))
+    )
+}
+
+#[test]
+fn test_expected_set_display_all() {
+    let mut all = ExpectedSet::new();
+    for tt in TokenType::all() {
+        let single = expected![&tt].to_string();
+        assert!(!single.is_empty(), "{:?} displays as an empty string", tt);
+        assert!(!single.contains(", "), "{:?} displays as {:?}", tt, single);
+        all.insert(&tt);
+    }
+
+    let display = all.to_string();
+    assert!(!display.starts_with(','), "{:?}", display);
+    assert!(!display.ends_with(' '), "{:?}", display);
+    assert!(!display.contains(", ,"), "{:?}", display);
+    assert_eq!(
+        all.as_vec()
+            .iter()
+            .map(|tt| tt.to_usize())
+            .collect::<Vec<usize>>(),
+        TokenType::all()
+            .iter()
+            .map(|tt| tt.to_usize())
+            .collect::<Vec<usize>>()
+    )
+}
+
+#[test]
+fn test_expected_set_as_vec_sorted() {
+    let set = expected![
+        &TokenType::Eof,
+        &TokenType::LParen,
+        &TokenType::Space,
+        &TokenType::Ident,
+        &TokenType::RParen
+    ];
+    assert_eq!(
+        set.as_vec(),
+        vec![
+            TokenType::Space,
+            TokenType::Ident,
+            TokenType::LParen,
+            TokenType::RParen,
+            TokenType::Eof
+        ]
+    )
+}
+
+#[test]
+fn test_parser_remaining1() {
+    let tokens = tokenize_str("x y").unwrap();
+    let builder = ExprBuilder::new();
+    let mut parser = Parser::new(&builder, &tokens);
+    assert_eq!(parser.remaining(), &tokens[..]);
+
+    assert_eq!(parser.require_ident(), Result::Ok("x"));
+    assert_eq!(
+        parser
+            .remaining()
+            .iter()
+            .map(|token| token.data)
+            .collect::<Vec<TokenData>>(),
+        vec![TokenData::Space, TokenData::Ident("y"), TokenData::Eof]
+    )
+}
+
+#[test]
+fn test_parser_wildcard() {
+    let input = String::from("\\_ -> x");
+    test_parser(input, &Expr::Lam(ast::syntax::WILDCARD, &Expr::Ident("x")))
+}
+
+#[test]
+fn test_parser_wildcard_expr() {
+    // `_` parses as an expression, but it's never bound.
+    let builder = ExprBuilder::new();
+    let expr = parse_str(&builder, "\\x -> _").unwrap();
+    assert_eq!(ast::syntax::free_vars(expr), vec![ast::syntax::WILDCARD])
+}
+
+#[test]
+fn test_definitions1() {
+    let tokens = tokenize_str("id = \\x -> x\nconst = \\x -> \\y ->\n  x\n").unwrap();
+    let builder = ExprBuilder::new();
+    let mut definitions = Parser::new(&builder, &tokens).definitions();
+    assert_eq!(
+        definitions.next(),
+        Option::Some(Result::Ok(Definition {
+            name: "id",
+            body: &Expr::Lam("x", &Expr::Ident("x"))
+        }))
+    );
+    assert_eq!(
+        definitions.next(),
+        Option::Some(Result::Ok(Definition {
+            name: "const",
+            body: &Expr::Lam("x", &Expr::Lam("y", &Expr::Ident("x")))
+        }))
+    );
+    assert_eq!(definitions.next(), Option::None)
+}
+
+#[test]
+fn test_definitions2() {
+    let tokens = tokenize_str("a = )\nb = b").unwrap();
+    let builder = ExprBuilder::new();
+    let mut definitions = Parser::new(&builder, &tokens).definitions();
+    assert!(matches!(definitions.next(), Option::Some(Result::Err(_))));
+    assert_eq!(definitions.next(), Option::None)
+}
+
+#[test]
+fn test_parse_program_recovering1() {
+    let tokens = tokenize_str("a = )\nb = x\nc = (y\n  z\nd = y").unwrap();
+    let builder = ExprBuilder::new();
+    let (definitions, errors) = Parser::new(&builder, &tokens).parse_program_recovering(10);
+    assert_eq!(
+        definitions
+            .iter()
+            .map(|definition| definition.name)
+            .collect::<Vec<_>>(),
+        vec!["b", "d"]
+    );
+    assert_eq!(
+        errors.iter().map(|err| err.offset()).collect::<Vec<_>>(),
+        vec![Offset(4), Offset(22)]
+    )
+}
+
+#[test]
+fn test_parse_program_recovering_max_errors() {
+    let tokens = tokenize_str("a = )\nb = )\nc = )\nd = )\ne = )\nf = x").unwrap();
+    let builder = ExprBuilder::new();
+    let (definitions, errors) = Parser::new(&builder, &tokens).parse_program_recovering(3);
+    assert_eq!(definitions, Vec::new());
+    assert_eq!(errors.len(), 4);
+    assert!(errors[..3]
+        .iter()
+        .all(|err| matches!(err, Error::Unexpected { .. })));
+    assert_eq!(errors[3], Error::TooManyErrors(Offset(22)));
+    assert_eq!(
+        errors[3].reportable().error.message,
+        "Too many errors; giving up"
+    )
+}
+
+#[test]
+fn test_definitions_newline() {
+    let tokens = tokenize_str("f = x\n\n-- g\ng = y\n").unwrap();
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        Parser::new(&builder, &tokens).parse_program(),
+        Result::Ok(vec![
+            Definition {
+                name: "f",
+                body: &Expr::Ident("x")
+            },
+            Definition {
+                name: "g",
+                body: &Expr::Ident("y")
+            }
+        ])
+    )
+}
+
+#[test]
+fn test_definitions_continued() {
+    let tokens = tokenize_str("f = x\n  -- y\n\n  y\n").unwrap();
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        Parser::new(&builder, &tokens).parse_program(),
+        Result::Ok(vec![Definition {
+            name: "f",
+            body: &Expr::App(&Expr::Ident("x"), &Expr::Ident("y"))
+        }])
+    )
+}
+
+#[test]
+fn test_parse_program1() {
+    let tokens = tokenize_str("x = y\n\ny = x").unwrap();
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        Parser::new(&builder, &tokens).parse_program(),
+        Result::Ok(vec![
+            Definition {
+                name: "x",
+                body: &Expr::Ident("y")
+            },
+            Definition {
+                name: "y",
+                body: &Expr::Ident("x")
+            }
+        ])
+    )
+}
+
+#[test]
+fn test_parse_empty_report() {
+    let mut src_files = span::SourceFiles::new();
+    src_files.new_source_file(String::from("test"), String::new());
+    let tokens = Lexer::from_source_file(src_files.get_by_name("test"))
+        .tokenize()
+        .unwrap();
+    let builder = ExprBuilder::new();
+    let err = Parser::new(&builder, &tokens).parse_expr_eof().unwrap_err();
+    let report = errors::build_report(&src_files, err.reportable());
+    assert!(report.message.starts_with("Unexpected end of input"))
+}
+
+#[test]
+fn test_error_boxed() {
+    let builder = ExprBuilder::new();
+    let tokens = tokenize_str("x )").unwrap();
+    let err = Parser::new(&builder, &tokens).parse_expr_eof().unwrap_err();
+    let message = "Unexpected ')', expecting one of: identifier, '(', ':', end of input";
+    assert_eq!(err.to_string(), message);
+    let owned: Box<dyn std::error::Error> = Box::new(OwnedError::from(err));
+    assert_eq!(owned.to_string(), message);
+
+    let err: Box<dyn std::error::Error> =
+        Box::new(OwnedError::from(parse_str(&builder, "?").unwrap_err()));
+    assert_eq!(err.to_string(), "Unexpected symbol '?'")
+}
+
+#[test]
+fn test_error_into_reportable() {
+    let err: Diagnostic = Error::UnexpectedEof(Offset(0)).into();
+    assert_eq!(err.error.message, "Unexpected end of input");
+
+    let builder = ExprBuilder::new();
+    let err: Diagnostic = parse_str(&builder, "?").unwrap_err().into();
+    assert_eq!(err.error.message, "Unexpected symbol '?'")
+}
+
+#[test]
+fn test_parser_no_juxtaposition() {
+    let config = ParserConfig {
+        allow_juxtaposition: false,
+        ..ParserConfig::default()
+    };
+    let builder = ExprBuilder::new();
+
+    let tokens = tokenize_str("x y").unwrap();
+    assert_eq!(
+        Parser::with_config(&builder, &tokens, config).parse_expr_eof(),
+        Result::Err(Error::Unexpected {
+            actual: Token::new(TokenData::Ident("y"), 2, 1),
+            expected: expected![&TokenType::Colon, &TokenType::Eof],
+        })
+    );
+
+    let tokens = tokenize_str("\\x -> x").unwrap();
+    assert_eq!(
+        Parser::with_config(&builder, &tokens, config).parse_expr_eof(),
+        Result::Ok(builder.mk_lam("x", builder.mk_ident("x")))
+    );
+
+    let tokens = tokenize_str("x y").unwrap();
+    assert_eq!(
+        Parser::new(&builder, &tokens).parse_expr_eof(),
+        Result::Ok(builder.mk_app(builder.mk_ident("x"), builder.mk_ident("y")))
+    )
+}
+
+#[test]
+fn test_parser_drop_parens() {
+    let config = ParserConfig {
+        keep_parens: false,
+        ..ParserConfig::default()
+    };
+    let builder = ExprBuilder::new();
+
+    let tokens = tokenize_str("(x)").unwrap();
+    assert_eq!(
+        Parser::with_config(&builder, &tokens, config).parse_expr_eof(),
+        Result::Ok(&Expr::Ident("x"))
+    );
+
+    let tokens = tokenize_str("f ((\\x -> x) y)").unwrap();
+    assert_eq!(
+        Parser::with_config(&builder, &tokens, config).parse_expr_eof(),
+        Result::Ok(builder.mk_app(
+            builder.mk_ident("f"),
+            builder.mk_app(
+                builder.mk_lam("x", builder.mk_ident("x")),
+                builder.mk_ident("y")
+            )
+        ))
+    )
+}
+
+#[cfg(test)]
+fn parse_idents<'src>(input: &'src str) -> ParseResult<'src, Vec<&'src str>> {
+    let tokens = tokenize_str(input).unwrap();
+    let builder = ExprBuilder::new();
+    let mut parser = Parser::new(&builder, &tokens);
+    let idents = parser.parse_separated(&TokenType::Comma, |this| {
+        let ident = this.require_ident()?;
+        let _ = this.ignore_whitespace();
+        Result::Ok(ident)
+    })?;
+    let _ = parser.require(&TokenType::Eof)?;
+    Result::Ok(idents)
+}
+
+#[test]
+fn test_parse_separated1() {
+    assert_eq!(parse_idents("x, y, z"), Result::Ok(vec!["x", "y", "z"]));
+    assert_eq!(parse_idents("x"), Result::Ok(vec!["x"]));
+    assert_eq!(parse_idents(""), Result::Ok(Vec::new()))
+}
+
+#[test]
+fn test_parse_separated2() {
+    assert_eq!(
+        parse_idents("x, y,"),
+        Result::Err(Error::Unexpected {
+            actual: Token::new(TokenData::Eof, 5, 0),
+            expected: expected![&TokenType::Ident],
+        })
+    );
+    assert_eq!(
+        parse_idents("x y"),
+        Result::Err(Error::Unexpected {
+            actual: Token::new(TokenData::Ident("y"), 2, 1),
+            expected: expected![&TokenType::Comma, &TokenType::Eof],
+        })
+    );
+    // An empty list, so the identifier is reported as expected too
+    assert_eq!(
+        parse_idents(")"),
+        Result::Err(Error::Unexpected {
+            actual: Token::new(TokenData::RParen, 0, 1),
+            expected: expected![&TokenType::Ident, &TokenType::Eof],
+        })
+    )
+}
+
+#[test]
+fn test_parser_fat_arrow() {
+    let builder = ExprBuilder::new();
+    let tokens = tokenize_str("\\x => x").unwrap();
+    assert_eq!(
+        Parser::new(&builder, &tokens).parse_expr_eof(),
+        Result::Ok(builder.mk_lam("x", builder.mk_ident("x")))
+    )
+}
+
+#[test]
+fn test_parser_missing_eof() {
+    let builder = ExprBuilder::new();
+
+    // Without the `Eof`, the error is the same as with it.
+    let tokens = tokenize_str("(x").unwrap();
+    let expected = Parser::new(&builder, &tokens).parse_expr_eof();
+    assert!(expected.is_err());
+    let without_eof = tokens[..tokens.len() - 1].to_vec();
+    assert_eq!(
+        Parser::new(&builder, &without_eof).parse_expr_eof(),
+        expected
+    );
+
+    let mut tokens = tokenize_str("x y").unwrap();
+    let _ = tokens.pop();
+    assert_eq!(
+        Parser::new(&builder, &tokens).parse_expr_eof(),
+        Result::Ok(builder.mk_app(builder.mk_ident("x"), builder.mk_ident("y")))
+    );
+
+    let tokens = Vec::new();
+    assert_eq!(
+        Parser::new(&builder, &tokens).parse_program(),
+        Result::Ok(Vec::new())
+    )
+}
+
+#[test]
+fn test_parser_ann1() {
+    let input = String::from("x : a");
+    test_parser(input, &Expr::Ann(&Expr::Ident("x"), &Type::TVar("a")))
+}
+
+#[test]
+fn test_parser_ann2() {
+    let input = String::from("(\\x -> x) : a -> a");
+    test_parser(
+        input,
+        &Expr::Ann(
+            &Expr::Parens(&Expr::Lam("x", &Expr::Ident("x"))),
+            &Type::TArrow(&Type::TVar("a"), &Type::TVar("a")),
+        ),
+    )
+}
+
+#[test]
+fn test_parser_too_deeply_nested() {
+    let input = format!("{}x{}", "(".repeat(100_000), ")".repeat(100_000));
+    let builder = ExprBuilder::new();
+    assert_eq!(
+        parse_str(&builder, &input),
+        Result::Err(ParseOrLexError::Parse(Error::TooDeeplyNested(Span {
+            start: Offset(256),
+            length: Offset(1)
+        })))
+    );
+
+    let input = format!("{}x{}", "(".repeat(256), ")".repeat(256));
+    assert!(parse_str(&builder, &input).is_ok())
+}
+
+#[test]
+fn test_parser_keyword_as_ident() {
+    // There are no number literals yet, so the `5` is skipped as a lexer error. The parser fails
+    // before it would get there anyway.
+    let (tokens, lex_errors) = Lexer::from_str("let = 5", Offset(0)).tokenize_recovering(1);
+    assert_eq!(lex_errors, vec![lexer::Error::Unexpected('5', Offset(6))]);
+    let builder = ExprBuilder::new();
+    let err = Parser::new(&builder, &tokens).parse_program().unwrap_err();
+    assert_eq!(
+        err,
+        Error::Unexpected {
+            actual: Token::new(TokenData::Let, 0, 3),
+            expected: expected![&TokenType::Ident],
+        }
+    );
+    let err = err.reportable();
+    assert_eq!(
+        err.error.message,
+        "Unexpected 'let', expecting one of: identifier"
+    );
+    assert_eq!(err.notes, vec![String::from("`let` is a reserved keyword")])
+}
+
+#[test]
+fn test_parse_eof_report() {
+    // The input ends with a space, so the caret goes after it.
+    let mut src_files = span::SourceFiles::new();
+    src_files.new_source_file(String::from("test"), String::from("(x "));
+    let tokens = Lexer::from_source_file(src_files.get_by_name("test"))
+        .tokenize()
+        .unwrap();
+    assert_eq!(
+        tokens.last(),
+        Option::Some(&Token::new(TokenData::Eof, 3, 0))
+    );
+    let builder = ExprBuilder::new();
+    let err = Parser::new(&builder, &tokens).parse_expr_eof().unwrap_err();
+    let report = errors::build_report(&src_files, err.reportable());
+    assert_eq!(report.source_line, "1 | (x ");
+    assert_eq!(report.caret_line, "  |    ^");
+    assert!(report.message.starts_with("Unexpected end of input"))
+}
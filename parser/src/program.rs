@@ -0,0 +1,100 @@
+use crate::{parse_str, ParseOrLexError};
+use ast::de_bruijn::{self, from_ast_checked, UnboundVariable};
+use ast::syntax;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<'src> {
+    Lex(lexer::Error),
+    Parse(crate::Error<'src>),
+    Unbound(Vec<UnboundVariable<'src>>),
+}
+
+impl<'src> From<ParseOrLexError<'src>> for Error<'src> {
+    fn from(err: ParseOrLexError<'src>) -> Self {
+        match err {
+            ParseOrLexError::Lex(err) => Error::Lex(err),
+            ParseOrLexError::Parse(err) => Error::Parse(err),
+        }
+    }
+}
+
+/// A parsed and de Bruijn converted expression that owns its nodes, so it can be passed around
+/// without keeping the source or any builders alive.
+pub struct Program {
+    /// Owns the nodes that `expr` points to. Allocated by `Box::into_raw` and freed when the
+    /// `Program` is dropped.
+    builder: *mut de_bruijn::ExprBuilder<'static>,
+    /// Actually borrows from `builder`; see [`Program::expr`].
+    expr: de_bruijn::ExprRef<'static>,
+}
+
+impl Program {
+    /// Lex, parse and convert `src`.
+    pub fn parse(src: &str) -> Result<Program, Error<'_>> {
+        let syntax_builder = syntax::ExprBuilder::new();
+        let ast = parse_str(&syntax_builder, src)?;
+
+        let builder = Box::into_raw(Box::new(de_bruijn::ExprBuilder::new()));
+        // SAFETY: `builder` came from `Box::into_raw`, so it's valid until it's passed to
+        // `Box::from_raw`, which only happens below on error or in `Drop`. No `Box` or `&mut`
+        // to the builder exists while this shared reference is used, so moving the `Program`
+        // only copies the pointer and doesn't invalidate the nodes. The `'static` reference
+        // never escapes: `expr` hands out references tied to `&self`.
+        let builder_ref: &'static de_bruijn::ExprBuilder<'static> = unsafe { &*builder };
+        match from_ast_checked(builder_ref, ast) {
+            Result::Ok(expr) => Result::Ok(Program { builder, expr }),
+            Result::Err(unbound) => {
+                // SAFETY: nothing borrowed from the builder outlives this branch.
+                drop(unsafe { Box::from_raw(builder) });
+                Result::Err(Error::Unbound(unbound))
+            }
+        }
+    }
+
+    pub fn expr(&self) -> de_bruijn::ExprRef<'_> {
+        self.expr
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        // SAFETY: `builder` came from `Box::into_raw` in `Program::parse` and is only freed
+        // here. The references handed out by `expr` borrow `self`, so none of them are live.
+        drop(unsafe { Box::from_raw(self.builder) });
+    }
+}
+
+#[test]
+fn test_program_parse1() {
+    use eval::env::Env;
+    use eval::heap::Heap;
+    use eval::value::Value;
+
+    let program = Program::parse("(\\x -> x) (\\y -> y)").unwrap();
+    let heap = Heap::with_capacity(1024);
+    assert_eq!(
+        eval::eval(&heap, &Vec::new(), program.expr()),
+        &Value::Closure {
+            env: Env::new(),
+            body: &de_bruijn::Expr::Var(0)
+        }
+    )
+}
+
+#[test]
+fn test_program_parse2() {
+    assert_eq!(
+        Program::parse("(\\x -> x) y").err(),
+        Option::Some(Error::Unbound(vec![UnboundVariable { name: "y" }]))
+    )
+}
+
+#[test]
+fn test_program_move() {
+    let programs: Vec<Program> = vec![Program::parse("\\x -> x").unwrap()];
+    let program = programs.into_iter().next().unwrap();
+    assert_eq!(
+        program.expr(),
+        &de_bruijn::Expr::Lam(&de_bruijn::Expr::Var(0))
+    )
+}
@@ -1,17 +1,49 @@
 use ast::de_bruijn;
 use ast::syntax;
+use ast::types;
+use eval::value::Value;
+use std::collections::HashMap;
+
+/// The glyphs used to print lambdas. A lambda is printed as `{lambda}{arg} {arrow} {body}`, so
+/// a `lambda` that's a word needs its own trailing space, as in `fn `.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrettyConfig<'a> {
+    pub lambda: &'a str,
+    pub arrow: &'a str,
+}
+
+impl<'a> Default for PrettyConfig<'a> {
+    /// The concrete syntax accepted by the parser: `\x -> x`.
+    fn default() -> Self {
+        PrettyConfig {
+            lambda: "\\",
+            arrow: "->",
+        }
+    }
+}
 
 pub fn pretty_syntax<'src, 'expr>(expr: syntax::ExprRef<'src, 'expr>) -> String {
+    pretty_syntax_with(&PrettyConfig::default(), expr)
+}
+
+pub fn pretty_syntax_with<'src, 'expr>(
+    config: &PrettyConfig,
+    expr: syntax::ExprRef<'src, 'expr>,
+) -> String {
     match expr {
         syntax::Expr::Ident(ident) => String::from(*ident),
         syntax::Expr::App(l, r) => {
             let parens_l = match &*l {
                 syntax::Expr::Lam(_, _) => true,
+                syntax::Expr::Let(_, _, _) => true,
+                syntax::Expr::Ann(_, _) => true,
                 _ => false,
             };
             let parens_r = match &*r {
                 syntax::Expr::Lam(_, _) => true,
                 syntax::Expr::App(_, _) => true,
+                syntax::Expr::Let(_, _, _) => true,
+                syntax::Expr::Ann(_, _) => true,
                 _ => false,
             };
             let mut string = String::new();
@@ -19,7 +51,7 @@ pub fn pretty_syntax<'src, 'expr>(expr: syntax::ExprRef<'src, 'expr>) -> String
             if parens_l {
                 string.push('(');
             }
-            string += &pretty_syntax(*l);
+            string += &pretty_syntax_with(config, *l);
             if parens_l {
                 string.push(')');
             }
@@ -29,7 +61,7 @@ pub fn pretty_syntax<'src, 'expr>(expr: syntax::ExprRef<'src, 'expr>) -> String
             if parens_r {
                 string.push('(');
             }
-            string += &pretty_syntax(*r);
+            string += &pretty_syntax_with(config, *r);
             if parens_r {
                 string.push(')');
             }
@@ -37,93 +69,417 @@ pub fn pretty_syntax<'src, 'expr>(expr: syntax::ExprRef<'src, 'expr>) -> String
             string
         }
         syntax::Expr::Lam(arg, body) => {
-            let mut string = String::from("\\");
+            let mut string = String::from(config.lambda);
             string += arg;
-            string += " -> ";
-            string += &pretty_syntax(*body);
+            string.push(' ');
+            string += config.arrow;
+            string.push(' ');
+            string += &pretty_syntax_with(config, *body);
+            string
+        }
+        syntax::Expr::Let(name, bound, body) => {
+            let mut string = String::from("let ");
+            string += name;
+            string += " = ";
+            string += &pretty_syntax_with(config, bound);
+            string += " in ";
+            string += &pretty_syntax_with(config, body);
             string
         }
         syntax::Expr::Parens(inner) => {
             let mut string = String::from("(");
-            string += &pretty_syntax(*inner);
+            string += &pretty_syntax_with(config, *inner);
+            string.push(')');
             string
         }
-    }
-}
-
-pub fn pretty_de_bruijn<'expr>(expr: de_bruijn::ExprRef<'expr>) -> String {
-    match expr {
-        de_bruijn::Expr::Var(ix) => format!("#{}", ix),
-        de_bruijn::Expr::U64(n) => format!("{}", n),
-        de_bruijn::Expr::App(l, r) => {
-            let parens_l = match &*l {
-                de_bruijn::Expr::Lam(_) => true,
-                _ => false,
-            };
-            let parens_r = match &*r {
-                de_bruijn::Expr::Lam(_) => true,
-                de_bruijn::Expr::App(_, _) => true,
-                _ => false,
-            };
+        syntax::Expr::Ann(inner, ty) => {
+            // A lambda or let would swallow the annotation, and annotations don't nest.
+            let parens = matches!(
+                inner,
+                syntax::Expr::Lam(_, _) | syntax::Expr::Let(_, _, _) | syntax::Expr::Ann(_, _)
+            );
             let mut string = String::new();
-
-            if parens_l {
+            if parens {
                 string.push('(');
             }
-            string += &pretty_de_bruijn(*l);
-            if parens_l {
+            string += &pretty_syntax_with(config, inner);
+            if parens {
                 string.push(')');
             }
+            string += " : ";
+            string += &pretty_type_with(config, ty);
+            string
+        }
+    }
+}
 
-            string.push(' ');
+pub fn pretty_type<'src, 'ty>(ty: types::TypeRef<'src, 'ty>) -> String {
+    pretty_type_with(&PrettyConfig::default(), ty)
+}
 
-            if parens_r {
+/// Arrows are printed with `config.arrow`.
+pub fn pretty_type_with<'src, 'ty>(config: &PrettyConfig, ty: types::TypeRef<'src, 'ty>) -> String {
+    match ty {
+        types::Type::TVar(name) => String::from(*name),
+        types::Type::TArrow(a, b) => {
+            let parens_a = matches!(a, types::Type::TArrow(_, _));
+            let mut string = String::new();
+            if parens_a {
                 string.push('(');
             }
-            string += &pretty_de_bruijn(*r);
-            if parens_r {
+            string += &pretty_type_with(config, a);
+            if parens_a {
                 string.push(')');
             }
-
+            string.push(' ');
+            string += config.arrow;
+            string.push(' ');
+            string += &pretty_type_with(config, b);
             string
         }
-        de_bruijn::Expr::AddU64(l, r) => {
-            let parens_l = match &*l {
-                de_bruijn::Expr::Lam(_) => true,
-                _ => false,
-            };
-            let parens_r = match &*r {
-                de_bruijn::Expr::Lam(_) => true,
-                de_bruijn::Expr::AddU64(_, _) => true,
-                _ => false,
-            };
-            let mut string = String::new();
+    }
+}
 
-            if parens_l {
-                string.push('(');
-            }
-            string += &pretty_de_bruijn(*l);
-            if parens_l {
-                string.push(')');
-            }
+/// Render `expr` like its `Debug` output, but only down to `max_depth` nodes from the root.
+/// Deeper subtrees are printed as `…`.
+pub fn debug_truncated<'src, 'expr>(
+    expr: syntax::ExprRef<'src, 'expr>,
+    max_depth: usize,
+) -> String {
+    if max_depth == 0 {
+        return String::from("…");
+    }
+    let depth = max_depth - 1;
+    match expr {
+        syntax::Expr::Ident(ident) => format!("Ident({:?})", ident),
+        syntax::Expr::Lam(arg, body) => format!("Lam({:?}, {})", arg, debug_truncated(body, depth)),
+        syntax::Expr::App(l, r) => format!(
+            "App({}, {})",
+            debug_truncated(l, depth),
+            debug_truncated(r, depth)
+        ),
+        syntax::Expr::Parens(inner) => format!("Parens({})", debug_truncated(inner, depth)),
+        syntax::Expr::Let(name, bound, body) => format!(
+            "Let({:?}, {}, {})",
+            name,
+            debug_truncated(bound, depth),
+            debug_truncated(body, depth)
+        ),
+        syntax::Expr::Ann(inner, ty) => {
+            format!("Ann({}, {:?})", debug_truncated(inner, depth), ty)
+        }
+    }
+}
 
-            string += " + ";
+pub fn pretty_de_bruijn<'expr>(expr: de_bruijn::ExprRef<'expr>) -> String {
+    pretty_de_bruijn_prec(expr, 0)
+}
 
-            if parens_r {
-                string.push('(');
-            }
-            string += &pretty_de_bruijn(*r);
-            if parens_r {
-                string.push(')');
-            }
+/// Print the named and de Bruijn forms of the same expression on two lines, with labels padded so
+/// that the expressions line up:
+///
+/// ```text
+/// named:     \x -> \y -> x
+/// de Bruijn: \. \. #1
+/// ```
+pub fn pretty_comparison<'src, 'expr>(
+    syntax_expr: syntax::ExprRef<'src, 'expr>,
+    de_bruijn_expr: de_bruijn::ExprRef<'expr>,
+) -> String {
+    format!(
+        "{:<11}{}\n{:<11}{}",
+        "named:",
+        pretty_syntax(syntax_expr),
+        "de Bruijn:",
+        pretty_de_bruijn(de_bruijn_expr)
+    )
+}
+
+/// How tightly an expression binds. Lambdas and lets extend as far right as possible, so they
+/// bind the loosest.
+fn de_bruijn_prec(expr: de_bruijn::ExprRef) -> u8 {
+    match expr {
+        de_bruijn::Expr::Lam(_) => 0,
+        de_bruijn::Expr::Let(_, _) => 0,
+        de_bruijn::Expr::AddU64(_, _) => 1,
+        de_bruijn::Expr::App(_, _) => 2,
+        de_bruijn::Expr::Fix(_) => 2,
+        de_bruijn::Expr::Var(_) => 3,
+        de_bruijn::Expr::U64(_) => 3,
+    }
+}
 
+/// Print `expr` in a context that requires precedence `prec`, adding parentheses if it binds
+/// less tightly than that.
+fn pretty_de_bruijn_prec(expr: de_bruijn::ExprRef, prec: u8) -> String {
+    let string = match expr {
+        de_bruijn::Expr::Var(ix) => format!("#{}", ix),
+        de_bruijn::Expr::U64(n) => format!("{}", n),
+        de_bruijn::Expr::App(l, r) => {
+            let mut string = pretty_de_bruijn_prec(l, 2);
+            string.push(' ');
+            string += &pretty_de_bruijn_prec(r, 3);
+            string
+        }
+        de_bruijn::Expr::AddU64(l, r) => {
+            let mut string = pretty_de_bruijn_prec(l, 1);
+            string += " + ";
+            string += &pretty_de_bruijn_prec(r, 2);
             string
         }
         de_bruijn::Expr::Lam(body) => {
             let mut string = String::from("\\");
             string += ". ";
-            string += &pretty_de_bruijn(*body);
+            string += &pretty_de_bruijn_prec(body, 0);
             string
         }
+        de_bruijn::Expr::Let(bound, body) => {
+            let mut string = String::from("let ");
+            string += &pretty_de_bruijn_prec(bound, 0);
+            string += " in ";
+            string += &pretty_de_bruijn_prec(body, 0);
+            string
+        }
+        de_bruijn::Expr::Fix(body) => {
+            let mut string = String::from("fix (");
+            string += &pretty_de_bruijn_prec(body, 0);
+            string.push(')');
+            string
+        }
+    };
+    if de_bruijn_prec(expr) < prec {
+        format!("({})", string)
+    } else {
+        string
+    }
+}
+
+/// If `expr` is a Church numeral `\f -> \x -> f (f ... x)`, get its number.
+fn church_numeral(expr: de_bruijn::ExprRef) -> Option<u64> {
+    match expr {
+        de_bruijn::Expr::Lam(de_bruijn::Expr::Lam(body)) => {
+            let mut n = 0;
+            let mut body = *body;
+            loop {
+                match body {
+                    de_bruijn::Expr::Var(0) => return Option::Some(n),
+                    de_bruijn::Expr::App(de_bruijn::Expr::Var(1), arg) => {
+                        n += 1;
+                        body = arg;
+                    }
+                    _ => return Option::None,
+                }
+            }
+        }
+        _ => Option::None,
+    }
+}
+
+/// If `expr` is a Church boolean `\t -> \f -> t` or `\t -> \f -> f`, get its value.
+fn church_boolean(expr: de_bruijn::ExprRef) -> Option<bool> {
+    match expr {
+        de_bruijn::Expr::Lam(de_bruijn::Expr::Lam(de_bruijn::Expr::Var(1))) => Option::Some(true),
+        de_bruijn::Expr::Lam(de_bruijn::Expr::Lam(de_bruijn::Expr::Var(0))) => Option::Some(false),
+        _ => Option::None,
+    }
+}
+
+/// Print a value, showing Church numerals and booleans as `42` or `true`. Other values are
+/// reified and printed as expressions. `\f -> \x -> x` is both zero and false, and is
+/// printed as `0`.
+pub fn pretty_value_smart(value: &Value) -> String {
+    let builder = de_bruijn::ExprBuilder::new();
+    let expr = eval::reify(&builder, value);
+    if let Option::Some(n) = church_numeral(expr) {
+        n.to_string()
+    } else if let Option::Some(b) = church_boolean(expr) {
+        b.to_string()
+    } else {
+        pretty_de_bruijn(expr)
     }
 }
+
+/// Render `expr` as a Graphviz digraph with an edge from each node to its children. Nodes are
+/// identified by address, so a subexpression that's shared (for example, by a hash-consing
+/// builder) is drawn once, with an edge from each of its parents.
+pub fn to_dot<'expr>(expr: de_bruijn::ExprRef<'expr>) -> String {
+    fn go<'expr>(
+        ids: &mut HashMap<*const de_bruijn::Expr<'expr>, usize>,
+        out: &mut String,
+        expr: de_bruijn::ExprRef<'expr>,
+    ) -> usize {
+        let key = expr as *const de_bruijn::Expr<'expr>;
+        if let Option::Some(id) = ids.get(&key) {
+            return *id;
+        }
+        let id = ids.len();
+        let _ = ids.insert(key, id);
+
+        let (label, children): (String, Vec<de_bruijn::ExprRef<'expr>>) = match expr {
+            de_bruijn::Expr::Var(n) => (format!("#{}", n), Vec::new()),
+            de_bruijn::Expr::Lam(body) => (String::from("\\\\"), vec![*body]),
+            de_bruijn::Expr::App(l, r) => (String::from("@"), vec![*l, *r]),
+            de_bruijn::Expr::U64(n) => (n.to_string(), Vec::new()),
+            de_bruijn::Expr::AddU64(l, r) => (String::from("+"), vec![*l, *r]),
+            de_bruijn::Expr::Let(bound, body) => (String::from("let"), vec![*bound, *body]),
+            de_bruijn::Expr::Fix(body) => (String::from("fix"), vec![*body]),
+        };
+        *out += &format!("  n{} [label=\"{}\"];\n", id, label);
+        for child in children {
+            let child_id = go(ids, out, child);
+            *out += &format!("  n{} -> n{};\n", id, child_id);
+        }
+        id
+    }
+
+    let mut out = String::from("digraph {\n");
+    let _ = go(&mut HashMap::new(), &mut out, expr);
+    out.push_str("}\n");
+    out
+}
+
+#[test]
+fn test_to_dot() {
+    // \. #0 #0
+    let builder = de_bruijn::ExprBuilder::new();
+    let expr = builder.mk_lam(builder.mk_app(builder.mk_var(0), builder.mk_var(0)));
+    let dot = to_dot(expr);
+    assert!(dot.starts_with("digraph {\n"));
+    assert!(dot.contains("  n0 [label=\"\\\\\"];\n"));
+    assert_eq!(dot.matches("[label=").count(), 4);
+    assert_eq!(dot.matches(" -> ").count(), 3);
+
+    // The two `#0`s are the same node when they're shared.
+    let builder = de_bruijn::ExprBuilder::with_hash_consing();
+    let expr = builder.mk_lam(builder.mk_app(builder.mk_var(0), builder.mk_var(0)));
+    let dot = to_dot(expr);
+    assert_eq!(dot.matches("[label=").count(), 3);
+    assert_eq!(dot.matches(" -> ").count(), 3);
+    assert_eq!(dot.matches("n1 -> n2;").count(), 2)
+}
+
+#[test]
+fn test_pretty_de_bruijn_app_add() {
+    // f (a + b)
+    let expr = &de_bruijn::Expr::App(
+        &de_bruijn::Expr::Var(2),
+        &de_bruijn::Expr::AddU64(&de_bruijn::Expr::Var(1), &de_bruijn::Expr::Var(0)),
+    );
+    assert_eq!(pretty_de_bruijn(expr), "#2 (#1 + #0)")
+}
+
+#[test]
+fn test_pretty_de_bruijn_add_lam() {
+    let expr = &de_bruijn::Expr::AddU64(
+        &de_bruijn::Expr::Lam(&de_bruijn::Expr::Var(0)),
+        &de_bruijn::Expr::Var(1),
+    );
+    assert_eq!(pretty_de_bruijn(expr), "(\\. #0) + #1")
+}
+
+#[test]
+fn test_pretty_de_bruijn_assoc() {
+    // (f x) (g y) and (1 + 2) + (3 + 4)
+    let app = &de_bruijn::Expr::App(
+        &de_bruijn::Expr::App(&de_bruijn::Expr::Var(0), &de_bruijn::Expr::Var(1)),
+        &de_bruijn::Expr::App(&de_bruijn::Expr::Var(2), &de_bruijn::Expr::Var(3)),
+    );
+    assert_eq!(pretty_de_bruijn(app), "#0 #1 (#2 #3)");
+    let add = &de_bruijn::Expr::AddU64(
+        &de_bruijn::Expr::AddU64(&de_bruijn::Expr::U64(1), &de_bruijn::Expr::U64(2)),
+        &de_bruijn::Expr::AddU64(&de_bruijn::Expr::U64(3), &de_bruijn::Expr::U64(4)),
+    );
+    assert_eq!(pretty_de_bruijn(add), "1 + 2 + (3 + 4)")
+}
+
+#[test]
+fn test_pretty_syntax_with_glyphs() {
+    let expr = &syntax::Expr::Lam("x", &syntax::Expr::Ident("x"));
+    let config = PrettyConfig {
+        lambda: "λ",
+        arrow: "→",
+    };
+    assert_eq!(pretty_syntax_with(&config, expr), "λx → x");
+    assert_eq!(pretty_syntax(expr), "\\x -> x")
+}
+
+#[test]
+fn test_pretty_comparison() {
+    // \x -> \y -> x
+    let syntax_expr = &syntax::Expr::Lam("x", &syntax::Expr::Lam("y", &syntax::Expr::Ident("x")));
+    let de_bruijn_expr = &de_bruijn::Expr::Lam(&de_bruijn::Expr::Lam(&de_bruijn::Expr::Var(1)));
+    assert_eq!(
+        pretty_comparison(syntax_expr, de_bruijn_expr),
+        "named:     \\x -> \\y -> x\nde Bruijn: \\. \\. #1"
+    )
+}
+
+#[test]
+fn test_pretty_syntax_ann() {
+    // f ((\x -> x) : (a -> a) -> a)
+    let a = &types::Type::TVar("a");
+    let expr = &syntax::Expr::App(
+        &syntax::Expr::Ident("f"),
+        &syntax::Expr::Ann(
+            &syntax::Expr::Lam("x", &syntax::Expr::Ident("x")),
+            &types::Type::TArrow(&types::Type::TArrow(a, a), a),
+        ),
+    );
+    assert_eq!(pretty_syntax(expr), "f ((\\x -> x) : (a -> a) -> a)")
+}
+
+#[test]
+fn test_debug_truncated() {
+    // \x -> (\y -> (y)) x
+    let expr = &syntax::Expr::Lam(
+        "x",
+        &syntax::Expr::App(
+            &syntax::Expr::Lam("y", &syntax::Expr::Parens(&syntax::Expr::Ident("y"))),
+            &syntax::Expr::Ident("x"),
+        ),
+    );
+    assert_eq!(syntax::depth(expr), 5);
+    assert_eq!(debug_truncated(expr, 2), "Lam(\"x\", App(…, …))");
+    assert_eq!(debug_truncated(expr, 5), format!("{:?}", expr))
+}
+
+#[test]
+fn test_pretty_value_smart_numeral() {
+    use eval::env::Env;
+
+    // the body of \f -> \x -> f (f (f x))
+    let body = &de_bruijn::Expr::Lam(&de_bruijn::Expr::App(
+        &de_bruijn::Expr::Var(1),
+        &de_bruijn::Expr::App(
+            &de_bruijn::Expr::Var(1),
+            &de_bruijn::Expr::App(&de_bruijn::Expr::Var(1), &de_bruijn::Expr::Var(0)),
+        ),
+    ));
+    let value = Value::Closure {
+        env: Env::new(),
+        body,
+    };
+    assert_eq!(pretty_value_smart(&value), "3")
+}
+
+#[test]
+fn test_pretty_value_smart_boolean() {
+    use eval::env::Env;
+
+    let value = Value::Closure {
+        env: Env::new(),
+        body: &de_bruijn::Expr::Lam(&de_bruijn::Expr::Var(1)),
+    };
+    assert_eq!(pretty_value_smart(&value), "true")
+}
+
+#[test]
+fn test_pretty_value_smart_other() {
+    use eval::env::Env;
+
+    let value = Value::Closure {
+        env: Env::new(),
+        body: &de_bruijn::Expr::App(&de_bruijn::Expr::Var(0), &de_bruijn::Expr::Var(0)),
+    };
+    assert_eq!(pretty_value_smart(&value), "\\. #0 #0")
+}
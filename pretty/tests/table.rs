@@ -0,0 +1,36 @@
+use ast::syntax::ExprBuilder;
+use parser::parse_str;
+use pretty::pretty_syntax;
+
+/// `(source, expected_pretty)`: lexing, parsing and pretty printing `source` gives
+/// `expected_pretty`. Parentheses in the source are kept, and spacing is normalised.
+const CASES: &[(&str, &str)] = &[
+    ("x", "x"),
+    ("\\x -> x", "\\x -> x"),
+    ("\\x->x", "\\x -> x"),
+    ("\\x -> \\y -> x", "\\x -> \\y -> x"),
+    ("f x y", "f x y"),
+    ("f   x\n  y", "f x y"),
+    ("(\\x -> x) y", "(\\x -> x) y"),
+    ("f (g x)", "f (g x)"),
+    ("((x))", "((x))"),
+    ("f (\\x -> x) y", "f (\\x -> x) y"),
+    ("x : a", "x : a"),
+    ("(\\x -> x) : a -> a", "(\\x -> x) : a -> a"),
+    ("f : (a -> b) -> a", "f : (a -> b) -> a"),
+];
+
+#[test]
+fn test_pretty_table() {
+    for (source, expected_pretty) in CASES {
+        let builder = ExprBuilder::new();
+        let expr = parse_str(&builder, source)
+            .unwrap_or_else(|err| panic!("failed to parse {:?}: {:?}", source, err));
+        assert_eq!(
+            &pretty_syntax(expr),
+            expected_pretty,
+            "pretty printing {:?}",
+            source
+        );
+    }
+}
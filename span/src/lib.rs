@@ -41,10 +41,28 @@ pub struct Span {
 }
 
 impl Span {
+    #[inline]
+    pub fn new(start: Offset, length: Offset) -> Self {
+        Span { start, length }
+    }
+
+    /// The smallest span that covers both `self` and `other`, including anything between them.
+    #[inline]
+    pub fn merge(&self, other: Span) -> Span {
+        let start = std::cmp::min(self.start, other.start);
+        let end = std::cmp::max(self.end(), other.end());
+        Span::new(start, end.subtract(start.to_u32()))
+    }
+
     #[inline]
     pub fn end(&self) -> Offset {
         self.start.add(self.length.to_u32())
     }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length.to_u32() == 0
+    }
 }
 
 #[derive(Debug)]
@@ -119,6 +137,13 @@ impl SourceFile {
             }
         }
 
+        // The offset just past the end is on the last line. This is where `Eof` tokens point, and
+        // it's the only offset in an empty file.
+        if !found && offset.to_usize() == content.len() {
+            found = true;
+            line_end = content.len();
+        }
+
         if found {
             Line {
                 offset: self.start.add(line_start.try_into().unwrap()),
@@ -132,9 +157,64 @@ impl SourceFile {
 }
 
 impl SourceFile {
+    /// Read a file on its own, without adding it to a [`SourceFiles`]. It starts at `Offset(0)`,
+    /// and its name is its path.
+    pub fn from_path(path: &Path) -> std::io::Result<SourceFile> {
+        let mut content = String::new();
+        let _ = __open_and_read(path, &mut content)?;
+        Result::Ok(SourceFile {
+            name: path.to_string_lossy().to_string(),
+            start: Offset(0),
+            content,
+        })
+    }
+
     pub fn data<'src>(&'src self) -> &'src str {
         &self.content
     }
+
+    pub fn content_bytes(&self) -> &[u8] {
+        self.content.as_bytes()
+    }
+
+    /// Get the text covered by `span`. Panics if `span` doesn't start and end on character
+    /// boundaries.
+    pub fn slice(&self, span: Span) -> &str {
+        let start = span.start.subtract(self.start.to_u32()).to_usize();
+        let end = span.end().subtract(self.start.to_u32()).to_usize();
+        if !(self.content.is_char_boundary(start) && self.content.is_char_boundary(end)) {
+            panic!("slice failed: {:?} is not on a character boundary", span)
+        }
+        &self.content[start..end]
+    }
+
+    /// The number of lines in the file. The text after the last newline is a line, even if it's
+    /// empty.
+    pub fn line_count(&self) -> u32 {
+        let newlines = self.content.chars().filter(is_newline).count();
+        (newlines + 1).try_into().unwrap()
+    }
+
+    /// The `Offset` of the beginning of line `line`, numbered from 1 as in [`Line`]. `None` if
+    /// the file doesn't have that line.
+    pub fn offset_of_line(&self, line: u32) -> Option<Offset> {
+        if line == 0 {
+            return Option::None;
+        }
+        if line == 1 {
+            return Option::Some(self.start);
+        }
+        let mut number = 1;
+        for (pos, c) in self.content.char_indices() {
+            if is_newline(&c) {
+                number += 1;
+                if number == line {
+                    return Option::Some(self.start.add((pos + 1).try_into().unwrap()));
+                }
+            }
+        }
+        Option::None
+    }
 }
 
 #[derive(Debug)]
@@ -166,7 +246,9 @@ impl SourceFiles {
         content: String,
     ) -> (Offset, String) {
         let start = self.next_addr;
-        self.next_addr = start.add(size.try_into().unwrap());
+        // Leave a one-byte gap after each file, so that the end of one file, where errors at the
+        // end of input are reported, isn't also the start of the next.
+        self.next_addr = start.add((size + 1).try_into().unwrap());
         let name_copy = name.clone();
         let src_file = SourceFile {
             name,
@@ -192,7 +274,8 @@ impl SourceFiles {
     }
 
     pub fn get_by_offset<'src>(&'src self, offset: Offset) -> &'src SourceFile {
-        if offset >= self.next_addr {
+        // The end of each file is allowed, so that errors at the end of input can be reported.
+        if offset >= self.next_addr || self.files.is_empty() {
             panic!("get_by_offset failed: offset out of bounds")
         }
         let ix = match self.files.binary_search_by_key(&offset, |file| file.start) {
@@ -210,6 +293,20 @@ impl SourceFiles {
         }
         panic!("get_by_name failed: no name {:?} found", name)
     }
+
+    /// The files in the order they were added.
+    pub fn iter(&self) -> std::slice::Iter<'_, SourceFile> {
+        self.files.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a SourceFiles {
+    type Item = &'a SourceFile;
+    type IntoIter = std::slice::Iter<'a, SourceFile>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.iter()
+    }
 }
 
 #[test]
@@ -242,28 +339,28 @@ fn test_get_by_offset1() {
     );
 
     assert_eq!(
-        src_files.get_by_offset(Offset(12)).data(),
+        src_files.get_by_offset(Offset(13)).data(),
         content_two.clone()
     );
     assert_eq!(
-        src_files.get_by_offset(Offset(14)).data(),
+        src_files.get_by_offset(Offset(15)).data(),
         content_two.clone()
     );
     assert_eq!(
-        src_files.get_by_offset(Offset(18)).data(),
+        src_files.get_by_offset(Offset(19)).data(),
         content_two.clone()
     );
 
     assert_eq!(
-        src_files.get_by_offset(Offset(19)).data(),
+        src_files.get_by_offset(Offset(21)).data(),
         content_three.clone()
     );
     assert_eq!(
-        src_files.get_by_offset(Offset(24)).data(),
+        src_files.get_by_offset(Offset(26)).data(),
         content_three.clone()
     );
     assert_eq!(
-        src_files.get_by_offset(Offset(31)).data(),
+        src_files.get_by_offset(Offset(33)).data(),
         content_three.clone()
     );
 }
@@ -369,3 +466,157 @@ fn test_get_line6() {
         }
     )
 }
+
+#[test]
+fn test_slice1() {
+    let src_file = SourceFile {
+        name: String::from("test"),
+        start: Offset(3),
+        content: String::from("a λx b"),
+    };
+    assert_eq!(
+        src_file.slice(Span {
+            start: Offset(5),
+            length: Offset(3)
+        }),
+        "λx"
+    )
+}
+
+#[test]
+#[should_panic(expected = "not on a character boundary")]
+fn test_slice2() {
+    let src_file = SourceFile {
+        name: String::from("test"),
+        start: Offset(0),
+        content: String::from("a λx b"),
+    };
+    src_file.slice(Span {
+        start: Offset(3),
+        length: Offset(1),
+    });
+}
+
+#[test]
+fn test_line_count() {
+    let src_file = SourceFile {
+        name: String::from("test"),
+        start: Offset(10),
+        content: String::from("ab\n\ncd\n"),
+    };
+    assert_eq!(src_file.line_count(), 4);
+}
+
+#[test]
+fn test_offset_of_line() {
+    let src_file = SourceFile {
+        name: String::from("test"),
+        start: Offset(10),
+        content: String::from("ab\n\ncd\n"),
+    };
+    assert_eq!(src_file.offset_of_line(0), Option::None);
+    assert_eq!(src_file.offset_of_line(1), Option::Some(Offset(10)));
+    assert_eq!(src_file.offset_of_line(2), Option::Some(Offset(13)));
+    assert_eq!(src_file.offset_of_line(3), Option::Some(Offset(14)));
+    // the empty line after the last newline
+    assert_eq!(src_file.offset_of_line(4), Option::Some(Offset(17)));
+    assert_eq!(src_file.offset_of_line(5), Option::None);
+}
+
+#[test]
+fn test_get_line_empty() {
+    let src_file = SourceFile {
+        name: String::from("test"),
+        start: Offset(4),
+        content: String::new(),
+    };
+    assert_eq!(
+        src_file.get_line(Offset(4)),
+        Line {
+            offset: Offset(4),
+            number: 1,
+            content: ""
+        }
+    )
+}
+
+#[test]
+fn test_get_line_end() {
+    let src_file = SourceFile {
+        name: String::from("test"),
+        start: Offset(0),
+        content: String::from("ab\ncd"),
+    };
+    assert_eq!(
+        src_file.get_line(Offset(5)),
+        Line {
+            offset: Offset(3),
+            number: 2,
+            content: "cd"
+        }
+    )
+}
+
+#[test]
+fn test_source_files_into_iter() {
+    let mut src_files = SourceFiles::new();
+    src_files.new_source_file(String::from("one"), String::from("first"));
+    src_files.new_source_file(String::from("two"), String::from("second"));
+
+    let mut names = Vec::new();
+    for file in &src_files {
+        names.push(file.name.as_str());
+    }
+    assert_eq!(names, vec!["one", "two"]);
+    assert_eq!(
+        src_files
+            .iter()
+            .map(|file| file.start)
+            .collect::<Vec<Offset>>(),
+        vec![Offset(0), Offset(6)]
+    )
+}
+
+#[test]
+fn test_get_by_offset_end_of_file() {
+    let mut src_files = SourceFiles::new();
+    let a = src_files.new_source_file(String::from("a"), String::from("(x"));
+    let empty = src_files.new_source_file(String::from("empty"), String::new());
+    let b = src_files.new_source_file(String::from("b"), String::from("y"));
+
+    // The end of a file belongs to that file, not to the next one.
+    assert_eq!(src_files.get_by_offset(a.add(2)).name, "a");
+    assert_eq!(src_files.get_by_offset(empty).name, "empty");
+    assert_eq!(src_files.get_by_offset(b).name, "b");
+    assert_eq!(src_files.get_by_offset(b.add(1)).name, "b");
+}
+
+#[test]
+fn test_span_merge() {
+    let a = Span::new(Offset(2), Offset(1));
+    let b = Span::new(Offset(3), Offset(1));
+    assert_eq!(a.merge(b), Span::new(Offset(2), Offset(2)));
+    assert_eq!(b.merge(a), Span::new(Offset(2), Offset(2)));
+
+    let c = Span::new(Offset(10), Offset(5));
+    assert_eq!(a.merge(c), Span::new(Offset(2), Offset(13)));
+    assert_eq!(c.merge(Span::new(Offset(11), Offset(0))), c);
+}
+
+#[test]
+fn test_source_file_from_path() {
+    let path = std::env::temp_dir().join(format!("span-from-path-{}.spd", std::process::id()));
+    std::fs::write(&path, "\\x -> x\n").unwrap();
+    let res = SourceFile::from_path(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let src_file = res.unwrap();
+    assert_eq!(src_file.name, path.to_string_lossy());
+    assert_eq!(src_file.start, Offset(0));
+    assert_eq!(src_file.content, "\\x -> x\n");
+
+    assert_eq!(
+        SourceFile::from_path(&path).unwrap_err().kind(),
+        std::io::ErrorKind::NotFound
+    )
+}